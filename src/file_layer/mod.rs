@@ -1 +1,3 @@
+pub(crate) mod mount;
+pub(crate) mod overlay;
 pub(crate) mod watcher;
@@ -0,0 +1,75 @@
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+/// Serve a directory under a URL path prefix, in addition to the root.
+///
+/// ```
+/// use live_server::Mount;
+///
+/// let mount: Mount = "/assets=../shared/static".parse().unwrap();
+/// assert_eq!(mount.prefix, "/assets");
+/// assert_eq!(mount.path, std::path::Path::new("../shared/static"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Mount {
+    /// URL path prefix to serve the directory under, like `/assets`
+    pub prefix: String,
+    /// Directory to serve
+    pub path: PathBuf,
+}
+
+impl FromStr for Mount {
+    type Err = String;
+
+    /// Parse a mount in the form of `PREFIX=PATH`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((prefix, path)) = s.split_once('=') else {
+            return Err(format!(
+                "Invalid mount `{s}`, expected the form of `/assets=../shared/static`"
+            ));
+        };
+        let prefix = prefix.trim().trim_end_matches('/');
+        if !prefix.starts_with('/') {
+            return Err(format!(
+                "Mount prefix `{prefix}` must start with `/` and must not be the root"
+            ));
+        }
+        Ok(Self {
+            prefix: prefix.to_string(),
+            path: PathBuf::from(path.trim()),
+        })
+    }
+}
+
+/// Find the directories serving `uri_path`, and the part of `uri_path` inside of them.
+///
+/// The mount with the longest matching prefix wins, and paths outside of all mounts
+/// are served from the `roots`. Prefixes only match whole path segments, so `/assets`
+/// matches `/assets/app.js` but not `/assets2/app.js`.
+pub(crate) fn resolve_mount<'a>(
+    mounts: &'a [Mount],
+    roots: &'a [PathBuf],
+    uri_path: &'a str,
+) -> (&'a [PathBuf], &'a str) {
+    mounts
+        .iter()
+        .filter_map(|mount| {
+            let rest = uri_path.strip_prefix(&mount.prefix)?;
+            (rest.is_empty() || rest.starts_with('/')).then_some((mount, rest))
+        })
+        .max_by_key(|(mount, _)| mount.prefix.len())
+        .map(|(mount, rest)| (std::slice::from_ref(&mount.path), rest))
+        .unwrap_or((roots, uri_path))
+}
+
+/// Find the directory containing `path` among the watched `roots`, preferring the
+/// innermost one if they are nested.
+pub(crate) fn find_root<'a>(roots: &'a [PathBuf], path: &Path) -> Option<&'a Path> {
+    roots
+        .iter()
+        .filter(|root| path.starts_with(root))
+        .max_by_key(|root| root.components().count())
+        .map(PathBuf::as_path)
+}
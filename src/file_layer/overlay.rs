@@ -0,0 +1,22 @@
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+
+/// Resolve `relative_path` in the first of `layers` containing it, or in the first layer
+/// if none does.
+///
+/// All layers share the same URL space, so that files of the later ones show through
+/// where the earlier ones have nothing, like `dist/` built on top of `public/`.
+pub(crate) async fn resolve_in_layers(layers: &[PathBuf], relative_path: &Path) -> PathBuf {
+    let path = layers[0].join(relative_path);
+    if layers.len() == 1 || fs::try_exists(&path).await.unwrap_or(false) {
+        return path;
+    }
+    for layer in &layers[1..] {
+        let overlay_path = layer.join(relative_path);
+        if fs::try_exists(&overlay_path).await.unwrap_or(false) {
+            return overlay_path;
+        }
+    }
+    path
+}
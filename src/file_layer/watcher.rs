@@ -13,7 +13,11 @@ use tokio::{
     },
 };
 
-use crate::utils::{is_ignored, strip_prefix};
+use crate::{
+    file_layer::mount::find_root,
+    http_layer::include::IncludedFiles,
+    utils::{is_ignored, strip_prefix},
+};
 
 pub(crate) async fn create_poll_watcher() -> Result<
     (
@@ -67,27 +71,40 @@ pub(crate) async fn create_recommended_watcher() -> Result<
     .map_err(|e| e.to_string())
 }
 
+/// Watch all `root_paths` and broadcast a reload when any of them changes.
+///
+/// Changes of ignored files only reload if they are `included_files` of pages.
 pub async fn watch<W: Watcher>(
-    root_path: PathBuf,
+    root_paths: Vec<PathBuf>,
     mut debouncer: Debouncer<W, RecommendedCache>,
     mut rx: Receiver<Result<Vec<DebouncedEvent>, Vec<Error>>>,
     tx: Arc<broadcast::Sender<()>>,
     ignore_files: bool,
+    included_files: IncludedFiles,
 ) {
-    debouncer
-        .watch(&root_path, RecursiveMode::Recursive)
-        .unwrap();
+    for root_path in &root_paths {
+        debouncer
+            .watch(root_path, RecursiveMode::Recursive)
+            .unwrap();
+    }
 
     while let Some(result) = rx.recv().await {
         let mut files_changed = false;
         match result {
             Ok(events) => {
                 for e in events {
-                    if ignore_files {
+                    let is_included = {
+                        let included_files = included_files.read().unwrap();
+                        e.paths.iter().any(|p| included_files.contains(p))
+                    };
+                    if ignore_files && !is_included {
                         match e
                             .paths
                             .iter()
-                            .map(|p| is_ignored(&root_path, p))
+                            .map(|p| match find_root(&root_paths, p) {
+                                Some(root_path) => is_ignored(root_path, p),
+                                None => Ok(false),
+                            })
                             .collect::<Result<Vec<_>, _>>()
                         {
                             Ok(ignored_list) => {
@@ -118,10 +135,14 @@ pub async fn watch<W: Watcher>(
                                     if let Both = kind {
                                         let source_name = &e.event.paths[0];
                                         let target_name = &e.event.paths[1];
+                                        let relative = |path| match find_root(&root_paths, path) {
+                                            Some(root_path) => strip_prefix(path, root_path),
+                                            None => path,
+                                        };
                                         log::debug!(
                                             "[RENAME] {} -> {}",
-                                            strip_prefix(source_name, &root_path).display(),
-                                            strip_prefix(target_name, &root_path).display(),
+                                            relative(source_name).display(),
+                                            relative(target_name).display(),
                                         );
                                         files_changed = true;
                                     }
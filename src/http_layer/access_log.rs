@@ -0,0 +1,305 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    iter,
+    net::{IpAddr, SocketAddr},
+    path::Path,
+    pin::Pin,
+    str::FromStr,
+    sync::{
+        Arc,
+        mpsc::{Sender, channel},
+    },
+    task::{Context, Poll},
+    thread,
+    time::{Instant, SystemTime},
+};
+
+use axum::{
+    body::{Body, Bytes},
+    extract::{ConnectInfo, Request, connect_info::Connected},
+    http::header,
+    middleware::Next,
+    response::Response,
+    serve::IncomingStream,
+};
+use http_body::{Frame, SizeHint};
+use tokio::net::TcpListener;
+
+use super::tls::TlsListener;
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Format of the access log.
+///
+/// ```
+/// use live_server::AccessLogFormat;
+///
+/// let format: AccessLogFormat = "json".parse().unwrap();
+/// assert_eq!(format, AccessLogFormat::Json);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AccessLogFormat {
+    /// The Common Log Format of web servers
+    Common,
+    /// The Common Log Format followed by the referer and the user agent
+    #[default]
+    Combined,
+    /// One JSON object per line, which also includes the duration of the request
+    Json,
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "common" => Ok(Self::Common),
+            "combined" => Ok(Self::Combined),
+            "json" => Ok(Self::Json),
+            _ => Err(format!(
+                "Invalid access log format `{s}`, expected `common`, `combined` or `json`"
+            )),
+        }
+    }
+}
+
+/// Destination of the access log.
+///
+/// Lines are written by a thread of their own, so that requests never wait for the disk.
+pub(crate) struct AccessLog {
+    format: AccessLogFormat,
+    lines: Sender<String>,
+}
+
+impl AccessLog {
+    /// Log to `path` if given, appending to the file, or to stderr otherwise.
+    pub(crate) fn new(format: AccessLogFormat, path: Option<&Path>) -> Result<Self, String> {
+        let writer: Box<dyn Write + Send> = match path {
+            Some(path) => Box::new(
+                OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| format!("Failed to open access log {path:?}: {err}"))?,
+            ),
+            None => Box::new(io::stderr()),
+        };
+        let (lines, receiver) = channel::<String>();
+        thread::spawn(move || {
+            let mut writer = BufWriter::new(writer);
+            // The thread stops once the server and with it the sender is dropped.
+            while let Ok(line) = receiver.recv() {
+                // Flush after each burst of requests rather than after every line.
+                let result = iter::once(line)
+                    .chain(receiver.try_iter())
+                    .try_for_each(|line| writer.write_all(line.as_bytes()))
+                    .and_then(|()| writer.flush());
+                if let Err(err) = result {
+                    log::error!("Failed to write access log: {err}");
+                }
+            }
+        });
+        Ok(Self { format, lines })
+    }
+
+    fn write(&self, entry: &Entry) {
+        let mut line = match self.format {
+            AccessLogFormat::Common => entry.common(),
+            AccessLogFormat::Combined => format!(
+                r#"{} "{}" "{}""#,
+                entry.common(),
+                escape(entry.referer.as_deref().unwrap_or("-")),
+                escape(entry.user_agent.as_deref().unwrap_or("-")),
+            ),
+            AccessLogFormat::Json => entry.json(),
+        };
+        line.push('\n');
+        // Sending only fails if the writer thread is gone, so there is nowhere to write to.
+        let _ = self.lines.send(line);
+    }
+}
+
+/// Log every request once its response body has been sent, or dropped if the client
+/// disconnects before that.
+pub(crate) async fn log_access(access_log: Arc<AccessLog>, req: Request, next: Next) -> Response {
+    let mut entry = new_entry(access_log, &req);
+    let response = next.run(req).await;
+    let (parts, body) = response.into_parts();
+    entry.status = parts.status.as_u16();
+    Response::from_parts(parts, Body::new(LoggedBody { inner: body, entry }))
+}
+
+fn new_entry(access_log: Arc<AccessLog>, req: &Request) -> Entry {
+    let header = |name| {
+        req.headers()
+            .get(name)
+            .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+    };
+    Entry {
+        client: req
+            .extensions()
+            .get::<ConnectInfo<ClientAddr>>()
+            .map(|ConnectInfo(ClientAddr(addr))| addr.ip()),
+        time: SystemTime::now(),
+        start: Instant::now(),
+        method: req.method().to_string(),
+        target: req
+            .uri()
+            .path_and_query()
+            .map_or_else(|| req.uri().path().to_string(), ToString::to_string),
+        version: format!("{:?}", req.version()),
+        referer: header(header::REFERER),
+        user_agent: header(header::USER_AGENT),
+        status: 0,
+        bytes: 0,
+        access_log,
+    }
+}
+
+/// Address of the client of a connection, over HTTP or HTTPS.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientAddr(SocketAddr);
+
+impl Connected<IncomingStream<'_, TcpListener>> for ClientAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for ClientAddr {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        Self(*stream.remote_addr())
+    }
+}
+
+/// A request being handled, which is written to the access log when dropped.
+struct Entry {
+    client: Option<IpAddr>,
+    time: SystemTime,
+    start: Instant,
+    method: String,
+    /// Path and query of the request
+    target: String,
+    version: String,
+    referer: Option<String>,
+    user_agent: Option<String>,
+    status: u16,
+    /// Bytes of the response body, after compression
+    bytes: u64,
+    access_log: Arc<AccessLog>,
+}
+
+impl Entry {
+    fn common(&self) -> String {
+        let client = self.client.map_or("-".to_string(), |ip| ip.to_string());
+        // The format uses `-` for empty bodies.
+        let bytes = match self.bytes {
+            0 => "-".to_string(),
+            bytes => bytes.to_string(),
+        };
+        format!(
+            r#"{client} - - [{}] "{} {} {}" {} {bytes}"#,
+            self.common_time(),
+            self.method,
+            escape(&self.target),
+            self.version,
+            self.status,
+        )
+    }
+
+    fn json(&self) -> String {
+        serde_json::json!({
+            "time": self.rfc3339_time(),
+            "client": self.client.map(|ip| ip.to_string()),
+            "method": self.method,
+            "path": self.target,
+            "version": self.version,
+            "status": self.status,
+            "bytes": self.bytes,
+            "duration_ms": self.start.elapsed().as_secs_f64() * 1000.0,
+            "referer": self.referer,
+            "user_agent": self.user_agent,
+        })
+        .to_string()
+    }
+
+    /// Split the time into day, month, year and `hh:mm:ss` in UTC.
+    fn time_parts(&self) -> (String, String, String, String) {
+        // Like `Sun, 06 Nov 1994 08:49:37 GMT`
+        let date = httpdate::fmt_http_date(self.time);
+        let mut parts = date.split(' ').skip(1).map(str::to_string);
+        let mut next = || parts.next().unwrap_or_default();
+        (next(), next(), next(), next())
+    }
+
+    /// Like `06/Nov/1994:08:49:37 +0000`
+    fn common_time(&self) -> String {
+        let (day, month, year, time) = self.time_parts();
+        format!("{day}/{month}/{year}:{time} +0000")
+    }
+
+    /// Like `1994-11-06T08:49:37Z`
+    fn rfc3339_time(&self) -> String {
+        let (day, month, year, time) = self.time_parts();
+        let month = MONTHS.iter().position(|m| *m == month).unwrap_or_default() + 1;
+        format!("{year}-{month:02}-{day}T{time}Z")
+    }
+}
+
+impl Drop for Entry {
+    fn drop(&mut self) {
+        self.access_log.write(self);
+    }
+}
+
+/// Escape quotes, backslashes and control characters of a quoted field.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.push_str(&format!("\\x{:02x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// A response body counting the bytes sent, keeping the size hint so that
+/// `Content-Length` is preserved.
+struct LoggedBody {
+    inner: Body,
+    entry: Entry,
+}
+
+impl http_body::Body for LoggedBody {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        let poll = Pin::new(&mut self.inner).poll_frame(cx);
+        if let Poll::Ready(Some(Ok(frame))) = &poll
+            && let Some(data) = frame.data_ref()
+        {
+            self.entry.bytes += data.len() as u64;
+        }
+        poll
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    extract::Request,
+    http::{HeaderValue, StatusCode, header},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use sha1::{Digest, Sha1};
+
+/// Users allowed to access the server with HTTP Basic authentication.
+///
+/// ```
+/// use live_server::BasicAuth;
+///
+/// let auth: BasicAuth = "admin:secret".parse().unwrap();
+/// assert!(auth.verify("admin", "secret"));
+/// assert!(!auth.verify("admin", "guess"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BasicAuth {
+    users: Vec<(String, PasswordHash)>,
+}
+
+#[derive(Debug, Clone)]
+enum PasswordHash {
+    Plain(String),
+    /// `{SHA}` followed by the base64 encoded SHA-1 digest, as created by `htpasswd -s`
+    Sha1(Vec<u8>),
+    /// bcrypt hash, as created by `htpasswd -B`
+    Bcrypt(String),
+}
+
+impl FromStr for BasicAuth {
+    type Err = String;
+
+    /// Parse a single user in the form of `USER:PASSWORD`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((user, password)) = s.split_once(':') else {
+            return Err("Invalid credentials, expected the form of `USER:PASSWORD`".to_string());
+        };
+        if user.is_empty() {
+            return Err("User name of the credentials must not be empty".to_string());
+        }
+        Ok(Self {
+            users: vec![(user.to_string(), PasswordHash::Plain(password.to_string()))],
+        })
+    }
+}
+
+impl BasicAuth {
+    /// Read the users from an htpasswd file.
+    ///
+    /// Passwords may be hashed with bcrypt (`htpasswd -B`) or SHA-1 (`htpasswd -s`), or
+    /// stored in plain text. Empty lines and lines starting with `#` are skipped.
+    pub fn from_htpasswd(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read htpasswd file {path:?}: {err}"))?;
+        let mut users = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let Some((user, hash)) = line.split_once(':') else {
+                return Err(format!("Invalid line {} in {path:?}", i + 1));
+            };
+            let hash = if let Some(digest) = hash.strip_prefix("{SHA}") {
+                let digest = STANDARD.decode(digest).map_err(|err| {
+                    format!("Invalid SHA-1 hash on line {} in {path:?}: {err}", i + 1)
+                })?;
+                PasswordHash::Sha1(digest)
+            } else if ["$2a$", "$2b$", "$2x$", "$2y$"]
+                .iter()
+                .any(|prefix| hash.starts_with(prefix))
+            {
+                PasswordHash::Bcrypt(hash.to_string())
+            } else if hash.starts_with('$') {
+                return Err(format!(
+                    "Unsupported password hash on line {} in {path:?}, use bcrypt (`htpasswd -B`) instead",
+                    i + 1
+                ));
+            } else {
+                PasswordHash::Plain(hash.to_string())
+            };
+            users.push((user.to_string(), hash));
+        }
+        if users.is_empty() {
+            return Err(format!("No users found in htpasswd file {path:?}"));
+        }
+        Ok(Self { users })
+    }
+
+    /// Check if the password of `user` is `password`.
+    ///
+    /// Checking bcrypt hashes is slow on purpose, so this should not be called in async
+    /// contexts directly.
+    pub fn verify(&self, user: &str, password: &str) -> bool {
+        self.users
+            .iter()
+            .filter(|(name, _)| name == user)
+            .any(|(_, hash)| match hash {
+                PasswordHash::Plain(expected) => {
+                    constant_time_eq(expected.as_bytes(), password.as_bytes())
+                }
+                PasswordHash::Sha1(expected) => {
+                    constant_time_eq(expected, &Sha1::digest(password.as_bytes()))
+                }
+                PasswordHash::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            })
+    }
+}
+
+/// Compare two byte strings in time depending only on their lengths.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// A [BasicAuth] with the `Authorization` headers that have been accepted, so that slow
+/// hashes are only checked once per client rather than once per request.
+pub(crate) struct Authenticator {
+    auth: BasicAuth,
+    accepted: Mutex<HashSet<HeaderValue>>,
+}
+
+impl Authenticator {
+    pub(crate) fn new(auth: BasicAuth) -> Self {
+        Self {
+            auth,
+            accepted: Mutex::new(HashSet::new()),
+        }
+    }
+
+    async fn is_authorized(self: &Arc<Self>, authorization: &HeaderValue) -> bool {
+        if self.accepted.lock().unwrap().contains(authorization) {
+            return true;
+        }
+        let Some((user, password)) = decode_basic_credentials(authorization) else {
+            return false;
+        };
+        let this = self.clone();
+        let is_authorized = tokio::task::spawn_blocking(move || this.auth.verify(&user, &password))
+            .await
+            .unwrap_or(false);
+        if is_authorized {
+            self.accepted.lock().unwrap().insert(authorization.clone());
+        }
+        is_authorized
+    }
+}
+
+/// Decode the user and password of an `Authorization: Basic ...` header.
+fn decode_basic_credentials(authorization: &HeaderValue) -> Option<(String, String)> {
+    let (scheme, credentials) = authorization.to_str().ok()?.trim().split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let credentials = STANDARD.decode(credentials.trim()).ok()?;
+    let credentials = String::from_utf8(credentials).ok()?;
+    let (user, password) = credentials.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// Reject requests without valid credentials, including the websocket upgrade.
+///
+/// Browsers send the credentials of an authenticated origin with the websocket handshake
+/// and `fetch` too, so the injected script keeps working after the login prompt.
+pub(crate) async fn require_auth(auth: Arc<Authenticator>, req: Request, next: Next) -> Response {
+    if let Some(authorization) = req.headers().get(header::AUTHORIZATION)
+        && auth.is_authorized(authorization).await
+    {
+        return next.run(req).await;
+    }
+    let mut response = (StatusCode::UNAUTHORIZED, "Authentication required").into_response();
+    response.headers_mut().insert(
+        header::WWW_AUTHENTICATE,
+        HeaderValue::from_static(r#"Basic realm="live-server", charset="UTF-8""#),
+    );
+    response
+}
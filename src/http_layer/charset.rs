@@ -0,0 +1,93 @@
+use super::html::tags;
+
+/// Only the beginning of a document is searched for `<meta>` tags, like browsers do.
+const PRESCAN_LIMIT: usize = 1024;
+
+/// Transcode a UTF-16 document with a byte order mark to UTF-8.
+///
+/// The injected script is ASCII, so it can be inserted into documents of ASCII
+/// compatible encodings byte-wise, but not into UTF-16 ones.
+pub(crate) fn utf16_to_utf8(bytes: &[u8]) -> Option<Vec<u8>> {
+    let from_bytes: fn([u8; 2]) -> u16 = match bytes {
+        [0xFF, 0xFE, ..] => u16::from_le_bytes,
+        [0xFE, 0xFF, ..] => u16::from_be_bytes,
+        _ => return None,
+    };
+    let units = bytes[2..]
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]))
+        .collect::<Vec<_>>();
+    Some(String::from_utf16_lossy(&units).into_bytes())
+}
+
+/// Detect the charset of an HTML document in an ASCII compatible encoding from its byte
+/// order mark, `<meta charset>` or `<meta http-equiv="Content-Type">`, like `shift_jis`.
+pub(crate) fn detect_charset(html: &[u8]) -> Option<String> {
+    if html.starts_with(&[0xEF, 0xBB, 0xBF]) {
+        return Some("utf-8".to_string());
+    }
+    let html = &html[..html.len().min(PRESCAN_LIMIT)];
+    for tag in tags(html).filter(|tag| tag.name == b"meta" && !tag.is_closing) {
+        let attributes = tag.attributes(html);
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|attribute| attribute.name == name)
+                .map(|attribute| attribute.value.as_str())
+        };
+        let charset = match attribute("charset") {
+            Some(charset) => Some(charset),
+            None if attribute("http-equiv")
+                .is_some_and(|v| v.eq_ignore_ascii_case("content-type")) =>
+            {
+                attribute("content").and_then(charset_of_content_type)
+            }
+            None => None,
+        };
+        if let Some(label) = charset.and_then(normalize_label) {
+            return Some(label);
+        }
+    }
+    None
+}
+
+/// Whether a label returned by [detect_charset] is one of UTF-8.
+pub(crate) fn is_utf8_label(label: &str) -> bool {
+    matches!(
+        label,
+        "utf-8"
+            | "utf8"
+            | "unicode-1-1-utf-8"
+            | "unicode11utf8"
+            | "unicode20utf8"
+            | "x-unicode20utf8"
+    )
+}
+
+/// Extract the charset of a `Content-Type` value like `text/html; charset=shift_jis`.
+fn charset_of_content_type(content_type: &str) -> Option<&str> {
+    let lowercase = content_type.to_ascii_lowercase();
+    let start = lowercase.find("charset=")? + "charset=".len();
+    let charset = content_type[start..].trim_start_matches(['"', '\'']);
+    let end = charset
+        .find(|c: char| c == ';' || c == '"' || c == '\'' || c.is_whitespace())
+        .unwrap_or(charset.len());
+    Some(&charset[..end])
+}
+
+/// Lowercase a charset label, rejecting anything that cannot be used in a header.
+///
+/// A UTF-16 declaration in a document without a byte order mark is treated as UTF-8,
+/// like browsers do.
+fn normalize_label(label: &str) -> Option<String> {
+    let label = label.trim().to_ascii_lowercase();
+    let is_valid = !label.is_empty()
+        && label
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'));
+    match label.as_str() {
+        _ if !is_valid => None,
+        "utf-16" | "utf-16le" | "utf-16be" => Some("utf-8".to_string()),
+        _ => Some(label),
+    }
+}
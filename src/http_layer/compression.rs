@@ -0,0 +1,245 @@
+use std::{
+    fs::Metadata,
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use async_compression::tokio::bufread::{BrotliEncoder, GzipEncoder, ZstdEncoder};
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{HeaderMap, HeaderValue, Method, StatusCode, header},
+    middleware::Next,
+    response::Response,
+};
+use futures::TryStreamExt;
+use tokio::fs::File;
+use tokio_util::io::{ReaderStream, StreamReader};
+
+use crate::http_layer::server::AppState;
+
+/// Responses smaller than this are not worth compressing.
+const MIN_COMPRESS_SIZE: u64 = 32;
+
+/// Content codings supported by live-server, in the order of preference.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Encoding {
+    Brotli,
+    Zstd,
+    Gzip,
+}
+
+impl Encoding {
+    const ALL: [Encoding; 3] = [Encoding::Brotli, Encoding::Zstd, Encoding::Gzip];
+
+    /// The token used in `Accept-Encoding` and `Content-Encoding`.
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zstd",
+            Encoding::Gzip => "gzip",
+        }
+    }
+
+    /// The file extension of a precompressed sibling file, like `foo.js.br`.
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            Encoding::Brotli => "br",
+            Encoding::Zstd => "zst",
+            Encoding::Gzip => "gz",
+        }
+    }
+}
+
+/// Parse `Accept-Encoding` into the supported encodings the client accepts,
+/// ordered by its quality values and then by our own preference.
+pub(crate) fn accepted_encodings(headers: &HeaderMap) -> Vec<Encoding> {
+    let Some(accept) = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok())
+    else {
+        return Vec::new();
+    };
+
+    let mut wildcard = None;
+    let mut qualities = [None; Encoding::ALL.len()];
+    for item in accept.split(',') {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or_default().trim();
+        let quality = params
+            .find_map(|param| param.trim().strip_prefix("q="))
+            .and_then(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+        if coding == "*" {
+            wildcard = Some(quality);
+        }
+        for (i, encoding) in Encoding::ALL.iter().enumerate() {
+            if coding.eq_ignore_ascii_case(encoding.as_str()) {
+                qualities[i] = Some(quality);
+            }
+        }
+    }
+
+    let mut encodings = Encoding::ALL
+        .iter()
+        .zip(qualities)
+        .filter_map(|(encoding, quality)| {
+            let quality = quality.or(wildcard)?;
+            (quality > 0.0).then_some((*encoding, quality))
+        })
+        .collect::<Vec<_>>();
+    // `sort_by` is stable, so encodings with the same quality keep our preference.
+    encodings.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    encodings
+        .into_iter()
+        .map(|(encoding, _)| encoding)
+        .collect()
+}
+
+/// Check if a response with this `Content-Type` benefits from compression.
+pub(crate) fn is_compressible(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    essence.starts_with("text/")
+        || essence.ends_with("+json")
+        || essence.ends_with("+xml")
+        || matches!(
+            essence.as_str(),
+            "application/javascript"
+                | "application/json"
+                | "application/xml"
+                | "application/wasm"
+                | "application/manifest+json"
+                | "image/svg+xml"
+                | "image/x-icon"
+                | "font/ttf"
+                | "font/otf"
+        )
+}
+
+/// Compress responses on the fly according to the `Accept-Encoding` of the request.
+///
+/// Responses that are already encoded (e.g. precompressed files), partial, empty or of
+/// an incompressible type are passed through untouched.
+pub(crate) async fn compress(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if !state.compression {
+        return next.run(req).await;
+    }
+    let encoding = accepted_encodings(req.headers()).first().copied();
+    let is_head = req.method() == Method::HEAD;
+    let mut response = next.run(req).await;
+
+    let headers = response.headers();
+    let is_compressible = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(is_compressible);
+    if !is_compressible || headers.contains_key(header::CONTENT_ENCODING) {
+        return response;
+    }
+    append_vary(response.headers_mut());
+
+    let headers = response.headers();
+    let status = response.status();
+    let is_small = headers
+        .get(header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok()?.parse::<u64>().ok())
+        .is_some_and(|len| len < MIN_COMPRESS_SIZE);
+    let Some(encoding) = encoding else {
+        return response;
+    };
+    if is_small
+        || status.is_informational()
+        || status == StatusCode::NO_CONTENT
+        || status == StatusCode::PARTIAL_CONTENT
+    {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    // The compressed body is not byte-for-byte identical to the file anymore,
+    // so only a weak tag is appropriate.
+    if let Some(etag) = parts.headers.get(header::ETAG)
+        && !etag.as_bytes().starts_with(b"W/")
+    {
+        let weak = [b"W/", etag.as_bytes()].concat();
+        parts
+            .headers
+            .insert(header::ETAG, HeaderValue::from_bytes(&weak).unwrap());
+    }
+    if status == StatusCode::NOT_MODIFIED {
+        return Response::from_parts(parts, body);
+    }
+
+    parts.headers.insert(
+        header::CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+    );
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.remove(header::ACCEPT_RANGES);
+    if is_head {
+        return Response::from_parts(parts, body);
+    }
+    Response::from_parts(parts, compress_body(body, encoding))
+}
+
+/// Tell caches that the response depends on `Accept-Encoding`, unless already done.
+pub(crate) fn append_vary(headers: &mut HeaderMap) {
+    let is_varied = headers
+        .get_all(header::VARY)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|v| {
+            v.trim()
+                .eq_ignore_ascii_case(header::ACCEPT_ENCODING.as_str())
+        });
+    if !is_varied {
+        headers.append(
+            header::VARY,
+            HeaderValue::from_static(header::ACCEPT_ENCODING.as_str()),
+        );
+    }
+}
+
+/// Open the first precompressed sibling of `path` (like `foo.js.br`) that matches
+/// one of the accepted `encodings`.
+pub(crate) async fn open_precompressed(
+    path: &Path,
+    encodings: &[Encoding],
+) -> Option<(Encoding, PathBuf, File, Metadata)> {
+    for encoding in encodings {
+        let mut sibling = path.as_os_str().to_owned();
+        sibling.push(".");
+        sibling.push(encoding.extension());
+        let sibling = PathBuf::from(sibling);
+        let Ok(file) = File::open(&sibling).await else {
+            continue;
+        };
+        match file.metadata().await {
+            Ok(metadata) if metadata.is_file() => {
+                return Some((*encoding, sibling, file, metadata));
+            }
+            _ => continue,
+        }
+    }
+    None
+}
+
+fn compress_body(body: Body, encoding: Encoding) -> Body {
+    let reader = StreamReader::new(body.into_data_stream().map_err(io::Error::other));
+    match encoding {
+        Encoding::Brotli => Body::from_stream(ReaderStream::new(BrotliEncoder::new(reader))),
+        Encoding::Zstd => Body::from_stream(ReaderStream::new(ZstdEncoder::new(reader))),
+        Encoding::Gzip => Body::from_stream(ReaderStream::new(GzipEncoder::new(reader))),
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    hash::{DefaultHasher, Hash, Hasher},
+    time::SystemTime,
+};
+
+use axum::http::{HeaderMap, header};
+
+/// Build a strong entity tag from the modification time and size of a file.
+pub(crate) fn file_etag(len: u64, modified: Option<SystemTime>) -> String {
+    let modified = modified
+        .and_then(|time| time.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!(
+        "\"{:x}.{:x}-{len:x}\"",
+        modified.as_secs(),
+        modified.subsec_nanos()
+    )
+}
+
+/// Build a strong entity tag from the content itself.
+///
+/// This is used for generated bodies like injected HTML, whose tag has to change
+/// whenever either the file or the injected script changes.
+pub(crate) fn content_etag(content: &[u8]) -> String {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+    format!("\"{:x}-{:x}\"", hasher.finish(), content.len())
+}
+
+/// Check `If-None-Match` and `If-Modified-Since` to decide if `304 Not Modified`
+/// can be sent instead of the body.
+///
+/// As required by RFC 9110, `If-Modified-Since` is ignored when `If-None-Match` is present.
+pub(crate) fn is_not_modified(
+    headers: &HeaderMap,
+    etag: &str,
+    modified: Option<SystemTime>,
+) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        return if_none_match
+            .to_str()
+            .is_ok_and(|value| etag_list_matches(value, etag, false));
+    }
+    let Some(modified) = modified else {
+        return false;
+    };
+    headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| httpdate::parse_http_date(value).ok())
+        .is_some_and(|since| unix_secs(modified) <= unix_secs(since))
+}
+
+/// Check if any tag of a comma-separated list matches `etag`, using the strong
+/// comparison of RFC 9110 if `strong` is set and the weak comparison otherwise.
+pub(crate) fn etag_list_matches(list: &str, etag: &str, strong: bool) -> bool {
+    if list.trim() == "*" {
+        return true;
+    }
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    list.split(',').any(|candidate| {
+        let candidate = candidate.trim();
+        if strong {
+            !candidate.starts_with("W/") && !etag.starts_with("W/") && candidate == etag
+        } else {
+            opaque(candidate) == opaque(etag)
+        }
+    })
+}
+
+/// Format a time as an HTTP date for the `Last-Modified` header.
+pub(crate) fn http_date(time: SystemTime) -> String {
+    httpdate::fmt_http_date(time)
+}
+
+/// HTTP dates only have a precision of one second.
+pub(crate) fn unix_secs(time: SystemTime) -> Option<u64> {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .ok()
+}
@@ -0,0 +1,184 @@
+use std::{collections::HashMap, path::Path, str::FromStr};
+
+use mime_guess::{Mime, mime};
+
+/// Only the beginning of a file is used to sniff its type.
+pub(crate) const SNIFF_LENGTH: u64 = 512;
+
+/// A MIME type for files with an extension, overriding the built-in one.
+///
+/// Overrides are parsed from `EXT=TYPE`:
+///
+/// ```
+/// use live_server::MimeOverride;
+///
+/// let mime: MimeOverride = ".webmanifest=application/manifest+json".parse().unwrap();
+/// assert_eq!(mime.extension, "webmanifest");
+/// assert_eq!(mime.mime, "application/manifest+json");
+/// ```
+#[derive(Debug, Clone)]
+pub struct MimeOverride {
+    /// Lowercase extension without the leading `.`, like `ts`
+    pub extension: String,
+    pub mime: Mime,
+}
+
+impl FromStr for MimeOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((extension, mime)) = s.split_once('=') else {
+            return Err(format!(
+                "Invalid MIME type override `{s}`, expected the form of `EXT=TYPE`"
+            ));
+        };
+        let extension = extension
+            .trim()
+            .trim_start_matches('.')
+            .to_ascii_lowercase();
+        if extension.is_empty() {
+            return Err(format!("MIME type override `{s}` has no extension"));
+        }
+        let mime = mime
+            .trim()
+            .parse()
+            .map_err(|err| format!("Invalid MIME type `{}`: {err}", mime.trim()))?;
+        Ok(Self { extension, mime })
+    }
+}
+
+impl MimeOverride {
+    /// Read overrides from a file of `EXT=TYPE` lines, or of `TYPE EXT...` lines like the
+    /// `mime.types` files of Apache and nginx.
+    ///
+    /// Empty lines and lines starting with `#` are skipped.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Vec<Self>, String> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read MIME types file {path:?}: {err}"))?;
+        let mut overrides = Vec::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim().trim_end_matches(';');
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.contains('=') {
+                overrides.push(
+                    line.parse()
+                        .map_err(|err| format!("{err} on line {} in {path:?}", i + 1))?,
+                );
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            let mime = words.next().unwrap_or_default();
+            for extension in words {
+                overrides.push(
+                    format!("{extension}={mime}")
+                        .parse()
+                        .map_err(|err| format!("{err} on line {} in {path:?}", i + 1))?,
+                );
+            }
+        }
+        Ok(overrides)
+    }
+}
+
+/// How the MIME types of files are determined.
+pub(crate) struct MimeTypes {
+    overrides: HashMap<String, Mime>,
+    default: Mime,
+    sniff: bool,
+}
+
+impl MimeTypes {
+    /// Later `overrides` of the same extension take precedence.
+    pub(crate) fn new(overrides: &[MimeOverride], default: Mime, sniff: bool) -> Self {
+        Self {
+            overrides: overrides
+                .iter()
+                .map(|o| (o.extension.clone(), o.mime.clone()))
+                .collect(),
+            default,
+            sniff,
+        }
+    }
+
+    /// Guess the type of `path` from the overrides, then from the built-in table, or use
+    /// the default type.
+    pub(crate) fn guess(&self, path: &Path) -> Mime {
+        let extension = path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_ascii_lowercase());
+        extension
+            .and_then(|extension| self.overrides.get(&extension).cloned())
+            .or_else(|| mime_guess::from_path(path).first())
+            .unwrap_or_else(|| self.default.clone())
+    }
+
+    /// Whether the type of `path` should be sniffed from its content, which is only done
+    /// for files without an extension.
+    pub(crate) fn should_sniff(&self, path: &Path) -> bool {
+        self.sniff && path.extension().is_none()
+    }
+}
+
+/// Whether responses of this type are HTML documents which get the reload script.
+pub(crate) fn is_html(mime: &Mime) -> bool {
+    mime.essence_str() == "text/html" || mime.essence_str() == "application/xhtml+xml"
+}
+
+/// Detect the type of a file from its first bytes, like browsers do for responses
+/// without a `Content-Type`.
+pub(crate) fn sniff(bytes: &[u8]) -> Mime {
+    const SIGNATURES: [(&[u8], &str); 10] = [
+        (b"%PDF-", "application/pdf"),
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"\0asm", "application/wasm"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\x1f\x8b\x08", "application/gzip"),
+        (b"wOFF", "font/woff"),
+        (b"wOF2", "font/woff2"),
+    ];
+    const HTML_PREFIXES: [&[u8]; 8] = [
+        b"<!doctype html",
+        b"<html",
+        b"<head",
+        b"<body",
+        b"<script",
+        b"<title",
+        b"<div",
+        b"<!--",
+    ];
+
+    if let Some((_, mime)) = SIGNATURES.iter().find(|(sig, _)| bytes.starts_with(sig)) {
+        return mime.parse().unwrap();
+    }
+    if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        return "image/webp".parse().unwrap();
+    }
+    let text = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let text = &text[text.iter().take_while(|b| b.is_ascii_whitespace()).count()..];
+    let starts_with = |prefix: &[u8]| {
+        text.len() >= prefix.len() && text[..prefix.len()].eq_ignore_ascii_case(prefix)
+    };
+    if HTML_PREFIXES.iter().any(|prefix| starts_with(prefix)) {
+        return mime::TEXT_HTML;
+    }
+    if starts_with(b"<svg") {
+        return mime::IMAGE_SVG;
+    }
+    if starts_with(b"<?xml") {
+        return mime::TEXT_XML;
+    }
+    let is_binary = text
+        .iter()
+        .any(|&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0c | 0x1b));
+    if is_binary {
+        mime::APPLICATION_OCTET_STREAM
+    } else {
+        mime::TEXT_PLAIN
+    }
+}
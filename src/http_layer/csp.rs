@@ -0,0 +1,77 @@
+/// Directives to amend so that the client of live-server works, each followed by the
+/// directives it falls back to if it is missing.
+const CLIENT_DIRECTIVES: [(&str, &[&str]); 4] = [
+    // The client script
+    ("script-src", &["default-src"]),
+    ("script-src-elem", &[]),
+    // The websocket and the preloading of assets
+    ("connect-src", &["default-src"]),
+    // The hidden iframe loading the updated page
+    ("frame-src", &["child-src", "default-src"]),
+];
+
+/// Amend a `Content-Security-Policy` so that it allows the client script of live-server,
+/// its websocket connection and the iframe it reloads the page with, all of which are
+/// same-origin.
+///
+/// Policies separated by commas are amended one by one.
+pub(crate) fn allow_client(policy: &str) -> String {
+    policy
+        .split(',')
+        .map(|policy| allow_client_in_policy(policy.trim()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn allow_client_in_policy(policy: &str) -> String {
+    let mut directives = policy
+        .split(';')
+        .map(str::trim)
+        .filter(|directive| !directive.is_empty())
+        .map(|directive| {
+            let mut parts = directive.split_ascii_whitespace();
+            let name = parts.next().unwrap_or_default().to_ascii_lowercase();
+            (name, parts.map(str::to_string).collect::<Vec<_>>())
+        })
+        .collect::<Vec<_>>();
+
+    for (name, fallbacks) in CLIENT_DIRECTIVES {
+        // Only the first occurrence of a directive is used by browsers.
+        if let Some((_, sources)) = directives.iter_mut().find(|(n, _)| n == name) {
+            allow_self(sources);
+            continue;
+        }
+        let fallback = fallbacks
+            .iter()
+            .find_map(|fallback| directives.iter().find(|(n, _)| n == fallback));
+        if let Some((_, sources)) = fallback {
+            let mut sources = sources.clone();
+            allow_self(&mut sources);
+            directives.push((name.to_string(), sources));
+        }
+    }
+
+    directives
+        .into_iter()
+        .map(|(name, sources)| {
+            let mut directive = name;
+            for source in sources {
+                directive.push(' ');
+                directive.push_str(&source);
+            }
+            directive
+        })
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+fn allow_self(sources: &mut Vec<String>) {
+    // `'none'` is only valid on its own.
+    sources.retain(|source| !source.eq_ignore_ascii_case("'none'"));
+    if !sources
+        .iter()
+        .any(|source| source.eq_ignore_ascii_case("'self'"))
+    {
+        sources.push("'self'".to_string());
+    }
+}
@@ -0,0 +1,130 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue, header},
+    middleware::Next,
+    response::Response,
+};
+use globset::{Glob, GlobMatcher};
+use percent_encoding::percent_decode_str;
+
+use super::csp::allow_client;
+
+/// A header added to responses, optionally only to URL paths matching a glob.
+///
+/// Rules are parsed from `Name: value` or `GLOB=Name: value`, where the glob is matched
+/// against the decoded URL path and `*` also matches `/`:
+///
+/// ```
+/// use live_server::HeaderRule;
+///
+/// let rule: HeaderRule = "*.wasm=Cache-Control: no-store".parse().unwrap();
+/// assert_eq!(rule.glob.as_deref(), Some("*.wasm"));
+/// assert_eq!(rule.name, "cache-control");
+///
+/// let rule: HeaderRule = "/fonts/**=Access-Control-Allow-Origin: *".parse().unwrap();
+/// assert_eq!(rule.value, "*");
+/// ```
+#[derive(Debug, Clone)]
+pub struct HeaderRule {
+    /// Only add the header to URL paths matching this glob, like `/fonts/**`
+    pub glob: Option<String>,
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl FromStr for HeaderRule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((scoped_name, value)) = s.split_once(':') else {
+            return Err(format!(
+                "Invalid header `{s}`, expected the form of `Name: value` or `GLOB=Name: value`"
+            ));
+        };
+        // Header names cannot contain `=`, so it always separates the glob.
+        let (glob, name) = match scoped_name.rsplit_once('=') {
+            Some((glob, name)) => {
+                let glob = glob.trim();
+                Glob::new(glob).map_err(|err| format!("Invalid glob `{glob}`: {err}"))?;
+                (Some(glob.to_string()), name)
+            }
+            None => (None, scoped_name),
+        };
+        let name = HeaderName::from_str(name.trim())
+            .map_err(|err| format!("Invalid header name `{}`: {err}", name.trim()))?;
+        let value = HeaderValue::from_str(value.trim())
+            .map_err(|err| format!("Invalid value of header `{name}`: {err}"))?;
+        Ok(Self { glob, name, value })
+    }
+}
+
+/// A [HeaderRule] with its glob compiled.
+pub(crate) struct CompiledHeaderRule {
+    matcher: Option<GlobMatcher>,
+    name: HeaderName,
+    value: HeaderValue,
+}
+
+pub(crate) fn compile_header_rules(rules: &[HeaderRule]) -> Vec<CompiledHeaderRule> {
+    rules
+        .iter()
+        .filter_map(|rule| {
+            let matcher = match &rule.glob {
+                Some(glob) => match Glob::new(glob) {
+                    Ok(glob) => Some(glob.compile_matcher()),
+                    Err(err) => {
+                        log::error!("Invalid glob `{glob}`: {err}");
+                        return None;
+                    }
+                },
+                None => None,
+            };
+            Some(CompiledHeaderRule {
+                matcher,
+                name: rule.name.clone(),
+                value: rule.value.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Add the headers of the matching rules to the response, replacing the ones set by
+/// live-server itself. Later rules take precedence over earlier ones.
+///
+/// `Content-Security-Policy` headers of HTML responses are amended to allow the client
+/// of live-server.
+pub(crate) async fn add_custom_headers(
+    rules: Arc<Vec<CompiledHeaderRule>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = percent_decode_str(req.uri().path())
+        .decode_utf8_lossy()
+        .into_owned();
+    let mut response = next.run(req).await;
+    // HTML responses carry the client script, which their policies must not block.
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("text/html"));
+    for rule in rules.iter() {
+        let is_matched = match &rule.matcher {
+            Some(matcher) => matcher.is_match(&path),
+            None => true,
+        };
+        if !is_matched {
+            continue;
+        }
+        let value = match rule.value.to_str() {
+            Ok(policy) if is_html && rule.name == header::CONTENT_SECURITY_POLICY => {
+                HeaderValue::from_str(&allow_client(policy)).unwrap_or(rule.value.clone())
+            }
+            _ => rule.value.clone(),
+        };
+        response.headers_mut().insert(rule.name.clone(), value);
+    }
+    response
+}
@@ -0,0 +1,96 @@
+use std::{net::IpAddr, sync::Arc};
+
+use axum::{
+    extract::{Request, State},
+    http::{HeaderMap, StatusCode, Uri, header, uri::Authority},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+
+use super::server::AppState;
+
+/// Reject requests whose `Host` is not allowed, to prevent
+/// [DNS rebinding](https://en.wikipedia.org/wiki/DNS_rebinding) attacks, in which a
+/// malicious website resolves its own domain to the address of live-server to read files.
+pub(crate) async fn check_host(
+    State(state): State<Arc<AppState>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if let Some(host) = request_host(req.headers(), req.uri())
+        && !is_allowed_authority(&state.allowed_hosts, host)
+    {
+        log::warn!("Rejected request to host `{host}`, which is not an allowed host");
+        let err_msg = format!(
+            "Host `{host}` is not allowed, restart live-server with `--allowed-host` to allow it"
+        );
+        return (StatusCode::FORBIDDEN, err_msg).into_response();
+    }
+    next.run(req).await
+}
+
+/// Check if the `Origin` of a request, like the websocket upgrade, is live-server itself
+/// or an allowed host.
+///
+/// Unlike `Host`, IP addresses and `localhost` are not always allowed, because other
+/// websites and local servers on them may send requests to live-server too. Requests
+/// without `Origin` are not sent by browsers on behalf of other websites, so they are
+/// allowed.
+pub(crate) fn is_allowed_origin(allowed_hosts: &[String], headers: &HeaderMap, uri: &Uri) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return true;
+    };
+    let Some(authority) = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.parse::<Uri>().ok())
+        .and_then(|uri| uri.authority().cloned())
+    else {
+        return false;
+    };
+    let is_same_origin = request_host(headers, uri)
+        .is_some_and(|host| host.eq_ignore_ascii_case(authority.as_str()));
+    is_same_origin || is_allowed_host(allowed_hosts, &authority.host().to_ascii_lowercase())
+}
+
+/// The authority a request is sent to, like `localhost:8080`.
+fn request_host<'a>(headers: &'a HeaderMap, uri: &'a Uri) -> Option<&'a str> {
+    // HTTP/2 requests carry the host in the URI instead of the header.
+    headers
+        .get(header::HOST)
+        .and_then(|v| v.to_str().ok())
+        .or_else(|| uri.authority().map(Authority::as_str))
+}
+
+/// Check if the host of `authority`, like `localhost:8080`, is allowed.
+///
+/// IP addresses, `localhost` and its subdomains are always allowed, because a website
+/// cannot rebind them. Allowed hosts starting with `.` also allow their subdomains, and
+/// `*` allows everything.
+fn is_allowed_authority(allowed_hosts: &[String], authority: &str) -> bool {
+    let Ok(authority) = authority.parse::<Authority>() else {
+        return false;
+    };
+    let host = authority.host().to_ascii_lowercase();
+    let host = host.trim_end_matches('.');
+    let ip = host.trim_start_matches('[').trim_end_matches(']');
+    if ip.parse::<IpAddr>().is_ok() || host == "localhost" || host.ends_with(".localhost") {
+        return true;
+    }
+    is_allowed_host(allowed_hosts, host)
+}
+
+/// Check if `host` matches one of `allowed_hosts`.
+fn is_allowed_host(allowed_hosts: &[String], host: &str) -> bool {
+    let host = host.trim_end_matches('.');
+    allowed_hosts.iter().any(|allowed| {
+        let allowed = allowed.to_ascii_lowercase();
+        if allowed == "*" {
+            return true;
+        }
+        match allowed.strip_prefix('.') {
+            Some(domain) => host == domain || host.ends_with(allowed.as_str()),
+            None => host == allowed,
+        }
+    })
+}
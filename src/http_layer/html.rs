@@ -0,0 +1,237 @@
+use std::ops::Range;
+
+/// Elements whose contents are text rather than markup, so tags inside of them are ignored.
+const RAW_TEXT_ELEMENTS: [&[u8]; 5] = [b"script", b"style", b"textarea", b"title", b"xmp"];
+
+/// A start or end tag found by [tags].
+pub(crate) struct Tag {
+    /// Lowercase tag name
+    pub(crate) name: Vec<u8>,
+    pub(crate) is_closing: bool,
+    /// Byte range of the whole tag, from `<` to `>`
+    pub(crate) range: Range<usize>,
+    /// Whether the tag is inside the inert contents of a `<template>`
+    pub(crate) in_template: bool,
+    attributes_start: usize,
+}
+
+/// An attribute of a [Tag].
+pub(crate) struct Attribute {
+    /// Lowercase attribute name
+    pub(crate) name: String,
+    pub(crate) value: String,
+    /// Byte range of the value including its quotes, or an empty range after the name if
+    /// there is no value
+    pub(crate) value_range: Range<usize>,
+}
+
+impl Tag {
+    pub(crate) fn attributes(&self, html: &[u8]) -> Vec<Attribute> {
+        parse_attributes(&html[..self.range.end], self.attributes_start)
+    }
+
+    pub(crate) fn attribute(&self, html: &[u8], name: &str) -> Option<Attribute> {
+        self.attributes(html)
+            .into_iter()
+            .find(|attribute| attribute.name == name)
+    }
+}
+
+/// Iterate over the tags of an HTML document, skipping comments, doctypes and the
+/// contents of raw text elements like `<script>`.
+///
+/// This is tolerant of malformed documents rather than a complete HTML parser, which is
+/// enough to find places to inject into.
+pub(crate) fn tags(html: &[u8]) -> Tags<'_> {
+    Tags {
+        html,
+        i: 0,
+        template_depth: 0,
+    }
+}
+
+pub(crate) struct Tags<'a> {
+    html: &'a [u8],
+    i: usize,
+    template_depth: usize,
+}
+
+impl Iterator for Tags<'_> {
+    type Item = Tag;
+
+    fn next(&mut self) -> Option<Tag> {
+        let html = self.html;
+        while let Some(offset) = html.get(self.i..)?.iter().position(|&b| b == b'<') {
+            let start = self.i + offset;
+            let rest = &html[start..];
+            if rest.starts_with(b"<!--") {
+                self.i = find(html, start + 4, b"-->").map_or(html.len(), |end| end + 3);
+                continue;
+            }
+            if rest.starts_with(b"<!") || rest.starts_with(b"<?") {
+                self.i = find(html, start + 2, b">").map_or(html.len(), |end| end + 1);
+                continue;
+            }
+
+            let is_closing = rest.get(1) == Some(&b'/');
+            let name_start = if is_closing { start + 2 } else { start + 1 };
+            if !html.get(name_start).is_some_and(u8::is_ascii_alphabetic) {
+                // A lone `<` in text
+                self.i = start + 1;
+                continue;
+            }
+            let name_len = html[name_start..]
+                .iter()
+                .take_while(|b| b.is_ascii_alphanumeric() || **b == b'-')
+                .count();
+            let name = html[name_start..name_start + name_len].to_ascii_lowercase();
+            let attributes_start = name_start + name_len;
+            let end = tag_end(html, attributes_start);
+            self.i = end;
+
+            if name == b"template" && is_closing {
+                self.template_depth = self.template_depth.saturating_sub(1);
+            }
+            let in_template = self.template_depth > 0;
+            if name == b"template" && !is_closing {
+                self.template_depth += 1;
+            } else if !is_closing && RAW_TEXT_ELEMENTS.contains(&name.as_slice()) {
+                let mut closing_tag = b"</".to_vec();
+                closing_tag.extend_from_slice(&name);
+                self.i = find_ignore_case(html, end, &closing_tag).unwrap_or(html.len());
+            }
+            return Some(Tag {
+                name,
+                is_closing,
+                range: start..end,
+                in_template,
+                attributes_start,
+            });
+        }
+        self.i = html.len();
+        None
+    }
+}
+
+/// Find the end of the tag whose attributes start at `from`, that is, right after the
+/// `>` outside of quoted attribute values.
+fn tag_end(html: &[u8], from: usize) -> usize {
+    let mut quote = None;
+    for (i, &b) in html.iter().enumerate().skip(from) {
+        match quote {
+            Some(q) if b == q => quote = None,
+            Some(_) => {}
+            None if b == b'"' || b == b'\'' => quote = Some(b),
+            None if b == b'>' => return i + 1,
+            None => {}
+        }
+    }
+    html.len()
+}
+
+/// Parse the attributes starting at `from` until `>` or the end of `html`.
+pub(crate) fn parse_attributes(html: &[u8], from: usize) -> Vec<Attribute> {
+    let mut attributes = Vec::new();
+    let mut i = from;
+    let skip_whitespace = |i: &mut usize| {
+        while *i < html.len() && html[*i].is_ascii_whitespace() {
+            *i += 1;
+        }
+    };
+    loop {
+        while i < html.len() && (html[i].is_ascii_whitespace() || html[i] == b'/') {
+            i += 1;
+        }
+        if i >= html.len() || html[i] == b'>' {
+            return attributes;
+        }
+        let name_start = i;
+        while i < html.len()
+            && !matches!(html[i], b'=' | b'>' | b'/')
+            && !html[i].is_ascii_whitespace()
+        {
+            i += 1;
+        }
+        let name = String::from_utf8_lossy(&html[name_start..i]).to_ascii_lowercase();
+        skip_whitespace(&mut i);
+        let (value, value_range) = if html.get(i) == Some(&b'=') {
+            i += 1;
+            skip_whitespace(&mut i);
+            let value_start = i;
+            let value = match html.get(i) {
+                Some(&quote @ (b'"' | b'\'')) => {
+                    let content_start = i + 1;
+                    let content_end = html[content_start..]
+                        .iter()
+                        .position(|&b| b == quote)
+                        .map_or(html.len(), |offset| content_start + offset);
+                    i = (content_end + 1).min(html.len());
+                    &html[content_start..content_end]
+                }
+                _ => {
+                    while i < html.len() && html[i] != b'>' && !html[i].is_ascii_whitespace() {
+                        i += 1;
+                    }
+                    &html[value_start..i]
+                }
+            };
+            (
+                decode_entities(&String::from_utf8_lossy(value)),
+                value_start..i,
+            )
+        } else {
+            (String::new(), i..i)
+        };
+        attributes.push(Attribute {
+            name,
+            value,
+            value_range,
+        });
+    }
+}
+
+/// Decode the character references that may appear in attributes like policies.
+fn decode_entities(value: &str) -> String {
+    if !value.contains('&') {
+        return value.to_string();
+    }
+    value
+        .replace("&quot;", "\"")
+        .replace("&#34;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Escape `text` to be used as the content of an element.
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Quote and escape `value` to be used as an attribute value.
+pub(crate) fn quote_attribute(value: &str) -> String {
+    let escaped = value
+        .replace('&', "&amp;")
+        .replace('"', "&quot;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    format!("\"{escaped}\"")
+}
+
+pub(crate) fn find(html: &[u8], from: usize, pattern: &[u8]) -> Option<usize> {
+    html.get(from..)?
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|offset| from + offset)
+}
+
+fn find_ignore_case(html: &[u8], from: usize, pattern: &[u8]) -> Option<usize> {
+    html.get(from..)?
+        .windows(pattern.len())
+        .position(|window| window.eq_ignore_ascii_case(pattern))
+        .map(|offset| from + offset)
+}
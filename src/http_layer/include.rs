@@ -0,0 +1,132 @@
+use std::{
+    collections::HashSet,
+    path::{Component, Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+use futures::{FutureExt, future::BoxFuture};
+use tokio::fs;
+
+use super::html::{Attribute, find, parse_attributes};
+use crate::file_layer::overlay::resolve_in_layers;
+
+const DIRECTIVE: &[u8] = b"<!--#include";
+
+/// Includes nested deeper than this are not resolved.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Files included by pages, so that changing them reloads the pages even if they are
+/// ignored.
+pub(crate) type IncludedFiles = Arc<RwLock<HashSet<PathBuf>>>;
+
+/// Replace the `<!--#include file="nav.html" -->` directives of an HTML page with the
+/// files they refer to, recursively.
+///
+/// `file` is relative to the including file and `virtual` to the root if it starts with
+/// `/`. Both are confined to the `layers` of the page, whose path inside of them is
+/// `relative_path`. Errors are left in the page as comments.
+pub(crate) async fn resolve_includes(
+    html: Vec<u8>,
+    layers: &[PathBuf],
+    relative_path: &Path,
+    included: &IncludedFiles,
+) -> Vec<u8> {
+    let mut stack = vec![relative_path.to_path_buf()];
+    expand(html, layers, included, &mut stack).await
+}
+
+fn expand<'a>(
+    html: Vec<u8>,
+    layers: &'a [PathBuf],
+    included: &'a IncludedFiles,
+    stack: &'a mut Vec<PathBuf>,
+) -> BoxFuture<'a, Vec<u8>> {
+    async move {
+        let mut expanded = Vec::with_capacity(html.len());
+        let mut copied = 0;
+        while let Some(start) = find(&html, copied, DIRECTIVE) {
+            let Some(end) = find(&html, start, b"-->") else {
+                break;
+            };
+            expanded.extend_from_slice(&html[copied..start]);
+            copied = end + 3;
+
+            let attributes = parse_attributes(&html[..end], start + DIRECTIVE.len());
+            let including_dir = stack
+                .last()
+                .and_then(|path| path.parent())
+                .unwrap_or(Path::new(""));
+            let relative_path = match include_path(&attributes, including_dir) {
+                Ok(path) => path,
+                Err(err_msg) => {
+                    expanded.extend_from_slice(&error_comment(&err_msg));
+                    continue;
+                }
+            };
+            if stack.contains(&relative_path) {
+                let err_msg = format!("Include cycle of {}", relative_path.display());
+                expanded.extend_from_slice(&error_comment(&err_msg));
+                continue;
+            }
+            if stack.len() > MAX_INCLUDE_DEPTH {
+                let err_msg = format!("Includes are nested deeper than {MAX_INCLUDE_DEPTH}");
+                expanded.extend_from_slice(&error_comment(&err_msg));
+                continue;
+            }
+
+            let path = resolve_in_layers(layers, &relative_path).await;
+            // Even missing files are tracked, so that creating them reloads the page.
+            included.write().unwrap().insert(path.clone());
+            match fs::read(&path).await {
+                Ok(content) => {
+                    stack.push(relative_path);
+                    let content = expand(content, layers, included, stack).await;
+                    stack.pop();
+                    expanded.extend_from_slice(&content);
+                }
+                Err(err) => {
+                    let err_msg = format!("Failed to include {}: {err}", relative_path.display());
+                    expanded.extend_from_slice(&error_comment(&err_msg));
+                }
+            }
+        }
+        expanded.extend_from_slice(&html[copied..]);
+        expanded
+    }
+    .boxed()
+}
+
+/// Resolve the `file` or `virtual` attribute of a directive to a path relative to the
+/// layers, rejecting anything outside of them.
+fn include_path(attributes: &[Attribute], including_dir: &Path) -> Result<PathBuf, String> {
+    let attribute = attributes
+        .iter()
+        .find(|attribute| attribute.name == "file" || attribute.name == "virtual")
+        .ok_or("Include directive without a `file` or `virtual` attribute")?;
+    let (base, value) = match attribute.value.strip_prefix('/') {
+        Some(value) if attribute.name == "virtual" => (Path::new(""), value),
+        _ => (including_dir, attribute.value.as_str()),
+    };
+
+    let mut path = base.to_path_buf();
+    for component in Path::new(value).components() {
+        match component {
+            Component::Normal(name) => path.push(name),
+            Component::CurDir => {}
+            Component::ParentDir if path.pop() => {}
+            _ => {
+                return Err(format!(
+                    "Include `{}` is outside of the root",
+                    attribute.value
+                ));
+            }
+        }
+    }
+    Ok(path)
+}
+
+fn error_comment(err_msg: &str) -> Vec<u8> {
+    log::warn!("{err_msg}");
+    // Comments cannot contain `--`.
+    format!("<!-- live-server: {} -->", err_msg.replace("--", "- -")).into_bytes()
+}
@@ -0,0 +1,202 @@
+use std::{ops::Range, str::FromStr};
+
+use axum::http::{HeaderMap, HeaderValue};
+
+use super::{
+    charset::{detect_charset, utf16_to_utf8},
+    csp::allow_client,
+    html::{Tag, quote_attribute, tags},
+};
+
+/// Where the reload script is injected into HTML documents.
+///
+/// ```
+/// use live_server::ScriptPosition;
+///
+/// let position: ScriptPosition = "head".parse().unwrap();
+/// assert_eq!(position, ScriptPosition::Head);
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ScriptPosition {
+    /// Before `</body>`, so that the page is parsed before the script runs
+    #[default]
+    Body,
+    /// Before `</head>`, so that the script runs before the scripts of the page
+    Head,
+}
+
+impl FromStr for ScriptPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "body" => Ok(Self::Body),
+            "head" => Ok(Self::Head),
+            _ => Err(format!(
+                "Invalid script position `{s}`, expected `body` or `head`"
+            )),
+        }
+    }
+}
+
+/// Fetch destinations of documents that the browser renders, rather than fragments
+/// fetched by scripts.
+const DOCUMENT_DESTINATIONS: [&str; 3] = ["document", "iframe", "frame"];
+
+/// Whether the client script is injected into an HTML response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Injection {
+    /// Inject into anything, for pages the browser renders as documents
+    Always,
+    /// Inject only into documents with an `<html>` or `<body>` tag, and not into fragments
+    DocumentsOnly,
+    /// Never inject, for fragments requested by scripts
+    Never,
+}
+
+impl Injection {
+    /// Decide from the headers of a request whether its response gets the client script.
+    ///
+    /// HTML partials requested by HTMX, Turbo Frames or `fetch()` must not get it, since it
+    /// would open another websocket connection whenever they are swapped into the page.
+    pub(crate) fn for_request(headers: &HeaderMap) -> Self {
+        if headers.contains_key("hx-request") || headers.contains_key("turbo-frame") {
+            return Self::Never;
+        }
+        match headers.get("sec-fetch-dest").and_then(|v| v.to_str().ok()) {
+            Some(dest) if DOCUMENT_DESTINATIONS.contains(&dest) => Self::Always,
+            Some(_) => Self::Never,
+            // Not sent by older browsers and other clients
+            None => Self::DocumentsOnly,
+        }
+    }
+}
+
+/// Inject `script` into an HTML document in any encoding, returning the document with its
+/// `Content-Type` of the `mime` essence, like `text/html`.
+///
+/// UTF-16 documents are transcoded to UTF-8. Documents in other encodings are kept as
+/// they are, and served with the charset they declare, or without any if they do not
+/// declare one and are not UTF-8, so that the browser detects it.
+pub(crate) fn inject_html(
+    html: Vec<u8>,
+    mime: &str,
+    script: &str,
+    position: ScriptPosition,
+    injection: Injection,
+) -> (Vec<u8>, HeaderValue) {
+    let (html, charset) = match utf16_to_utf8(&html) {
+        Some(html) => (html, Some("utf-8".to_string())),
+        None => {
+            let charset = detect_charset(&html).or_else(|| {
+                std::str::from_utf8(&html)
+                    .is_ok()
+                    .then(|| "utf-8".to_string())
+            });
+            (html, charset)
+        }
+    };
+    let content_type = match charset {
+        Some(charset) => format!("{mime}; charset={charset}"),
+        None => mime.to_string(),
+    };
+    let html = match injection {
+        Injection::Never => html,
+        _ => inject_script(html, script, position, injection),
+    };
+    (
+        html,
+        // Charsets are validated by `detect_charset`.
+        HeaderValue::from_str(&content_type).unwrap(),
+    )
+}
+
+/// Insert `script` into `html` before `</body>` or `</head>` according to `position`,
+/// before the other one if the preferred tag is missing, or at the end if both are.
+///
+/// The script reuses the nonce of the scripts of the page, and `<meta>` policies are
+/// amended to allow it. This works on bytes, so any ASCII compatible encoding is fine.
+fn inject_script(
+    html: Vec<u8>,
+    script: &str,
+    position: ScriptPosition,
+    injection: Injection,
+) -> Vec<u8> {
+    let mut is_document = false;
+    let mut head = None;
+    let mut body = None;
+    let mut nonce = None;
+    let mut edits = Vec::new();
+    for tag in tags(&html).filter(|tag| !tag.in_template) {
+        if tag.name == b"html" || tag.name == b"body" {
+            is_document = true;
+        }
+        match (tag.name.as_slice(), tag.is_closing) {
+            (b"head", true) => head = head.or(Some(tag.range.start)),
+            (b"body", true) => body = Some(tag.range.start),
+            (b"script", false) if nonce.is_none() => {
+                nonce = tag
+                    .attribute(&html, "nonce")
+                    .map(|attribute| attribute.value)
+                    .filter(|nonce| is_valid_nonce(nonce));
+            }
+            (b"meta", false) => edits.extend(amend_meta_policy(&html, &tag)),
+            _ => {}
+        }
+    }
+
+    if injection == Injection::DocumentsOnly && !is_document {
+        return html;
+    }
+
+    let index = match position {
+        ScriptPosition::Body => body.or(head),
+        ScriptPosition::Head => head.or(body),
+    }
+    .unwrap_or(html.len());
+    let script = match nonce {
+        Some(nonce) => script.replacen("<script ", &format!("<script nonce=\"{nonce}\" "), 1),
+        None => script.to_string(),
+    };
+    edits.push((index..index, script));
+    edits.sort_by_key(|(range, _)| range.start);
+
+    let mut injected =
+        Vec::with_capacity(html.len() + edits.iter().map(|e| e.1.len()).sum::<usize>());
+    let mut copied = 0;
+    for (range, replacement) in edits {
+        injected.extend_from_slice(&html[copied..range.start]);
+        injected.extend_from_slice(replacement.as_bytes());
+        copied = range.end;
+    }
+    injected.extend_from_slice(&html[copied..]);
+    injected
+}
+
+/// Replace the policy of a `<meta http-equiv="Content-Security-Policy">` with one that
+/// allows the client of live-server.
+fn amend_meta_policy(html: &[u8], tag: &Tag) -> Option<(Range<usize>, String)> {
+    let attributes = tag.attributes(html);
+    let is_policy = attributes.iter().any(|attribute| {
+        attribute.name == "http-equiv"
+            && attribute
+                .value
+                .eq_ignore_ascii_case("content-security-policy")
+    });
+    if !is_policy {
+        return None;
+    }
+    let content = attributes
+        .into_iter()
+        .find(|attribute| attribute.name == "content")?;
+    let policy = allow_client(&content.value);
+    Some((content.value_range, quote_attribute(&policy)))
+}
+
+/// Nonces are base64 encoded, so anything else is not reused in the injected tag.
+fn is_valid_nonce(nonce: &str) -> bool {
+    !nonce.is_empty()
+        && nonce
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'+' | b'/' | b'=' | b'-' | b'_'))
+}
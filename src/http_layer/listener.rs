@@ -5,27 +5,7 @@ use tokio::net::TcpListener;
 
 pub(crate) async fn create_listener(addr: &str) -> Result<TcpListener, String> {
     match tokio::net::TcpListener::bind(&addr).await {
-        Ok(listener) => {
-            let port = listener.local_addr().unwrap().port();
-            let host = listener.local_addr().unwrap().ip();
-            let host = match host.is_unspecified() {
-                true => match local_ip() {
-                    Ok(addr) => addr,
-                    Err(err) => {
-                        log::warn!("Failed to get local IP address: {err}");
-                        host
-                    }
-                },
-                false => host,
-            };
-
-            let addr = match host {
-                IpAddr::V4(host) => format!("{host}:{port}"),
-                IpAddr::V6(host) => format!("[{host}]:{port}"),
-            };
-            log::info!("Listening on http://{addr}/");
-            Ok(listener)
-        }
+        Ok(listener) => Ok(listener),
         Err(err) => {
             let err_msg = if let std::io::ErrorKind::AddrInUse = err.kind() {
                 format!("Address {} is already in use", addr)
@@ -37,3 +17,25 @@ pub(crate) async fn create_listener(addr: &str) -> Result<TcpListener, String> {
         }
     }
 }
+
+/// Log the link of the listener, using `scheme` (`http` or `https`).
+pub(crate) fn print_listening_on_link(listener: &TcpListener, scheme: &str) {
+    let port = listener.local_addr().unwrap().port();
+    let host = listener.local_addr().unwrap().ip();
+    let host = match host.is_unspecified() {
+        true => match local_ip() {
+            Ok(addr) => addr,
+            Err(err) => {
+                log::warn!("Failed to get local IP address: {err}");
+                host
+            }
+        },
+        false => host,
+    };
+
+    let addr = match host {
+        IpAddr::V4(host) => format!("{host}:{port}"),
+        IpAddr::V6(host) => format!("[{host}]:{port}"),
+    };
+    log::info!("Listening on {scheme}://{addr}/");
+}
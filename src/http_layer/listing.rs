@@ -0,0 +1,370 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use mime_guess::mime;
+use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+
+use crate::{
+    http_layer::{
+        conditional::{http_date, unix_secs},
+        html::escape_html,
+    },
+    utils::is_ignored,
+};
+
+/// Recursive JSON listings stop at this depth, which also stops symlink loops.
+const MAX_DEPTH: usize = 32;
+
+/// Characters that must be escaped when a file name is used as one URL path segment.
+const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'&')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'[')
+    .add(b'\\')
+    .add(b']')
+    .add(b'^')
+    .add(b'`')
+    .add(b'{')
+    .add(b'|')
+    .add(b'}');
+
+/// Column a listing is sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum SortKey {
+    #[default]
+    Name,
+    Size,
+    Modified,
+}
+
+impl SortKey {
+    const ALL: [SortKey; 3] = [SortKey::Name, SortKey::Size, SortKey::Modified];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            SortKey::Name => "name",
+            SortKey::Size => "size",
+            SortKey::Modified => "modified",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            SortKey::Name => "Name",
+            SortKey::Size => "Size",
+            SortKey::Modified => "Modified",
+        }
+    }
+}
+
+/// Order of a listing, from the `sort` and `order` query parameters like
+/// `?sort=size&order=desc`, so that sorting works without JavaScript.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct ListingSort {
+    pub(crate) key: SortKey,
+    pub(crate) descending: bool,
+}
+
+impl ListingSort {
+    /// Parse the query of a request, ignoring unknown parameters and values.
+    pub(crate) fn from_query(query: Option<&str>) -> Self {
+        let mut sort = Self::default();
+        for pair in query.unwrap_or_default().split('&') {
+            match pair.split_once('=') {
+                Some(("sort", key)) => {
+                    if let Some(key) = SortKey::ALL.into_iter().find(|k| k.as_str() == key) {
+                        sort.key = key;
+                    }
+                }
+                Some(("order", order)) => sort.descending = order == "desc",
+                _ => {}
+            }
+        }
+        sort
+    }
+}
+
+/// Representation of a listing requested with `Accept: application/json` or
+/// `?format=json`, optionally including subdirectories with `?recursive` or `?depth=2`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct JsonListing {
+    /// Levels of directories to list, where `1` is only the requested one
+    pub(crate) depth: usize,
+}
+
+impl JsonListing {
+    /// Return `None` if the request does not ask for JSON.
+    pub(crate) fn from_request(accept: Option<&str>, query: Option<&str>) -> Option<Self> {
+        let mut is_json = accept.is_some_and(|accept| accept.contains("application/json"));
+        let mut depth = 1;
+        for pair in query.unwrap_or_default().split('&') {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            match name {
+                "format" => is_json = value == "json",
+                "recursive" if value != "false" => depth = depth.max(MAX_DEPTH),
+                "depth" => {
+                    if let Ok(value) = value.parse::<usize>() {
+                        depth = value.clamp(1, MAX_DEPTH);
+                    }
+                }
+                _ => {}
+            }
+        }
+        is_json.then_some(Self { depth })
+    }
+}
+
+/// An entry of a directory listing.
+pub(crate) struct ListingEntry {
+    pub(crate) name: String,
+    pub(crate) is_dir: bool,
+    /// Size in bytes, which is zero for directories
+    pub(crate) size: u64,
+    pub(crate) modified: Option<SystemTime>,
+}
+
+/// Read the entries of `relative_dir` merged from all `layers`.
+///
+/// This does blocking IO, so it should be run with [tokio::task::spawn_blocking].
+pub(crate) fn read_entries(
+    layers: &[PathBuf],
+    relative_dir: &Path,
+    auto_ignore: bool,
+) -> std::io::Result<Vec<ListingEntry>> {
+    // Entries of earlier layers shadow the ones of later layers with the same name.
+    let mut entries = HashMap::new();
+    for layer in layers {
+        let directory = layer.join(relative_dir);
+        if !directory.is_dir() {
+            continue;
+        }
+        for entry in std::fs::read_dir(directory)?.flatten() {
+            let Ok(name) = entry.file_name().into_string() else {
+                continue;
+            };
+            if entries.contains_key(&name) {
+                continue;
+            }
+            if auto_ignore {
+                match is_ignored(layer, &entry.path()) {
+                    Ok(ignored) => {
+                        if ignored {
+                            continue;
+                        }
+                    }
+                    Err(err) => {
+                        log::error!("Failed to check ignore files: {err}");
+                        // Do nothing if we cannot know if it's an ignored entry
+                        continue;
+                    }
+                }
+            }
+            let Ok(metadata) = entry.metadata() else {
+                continue;
+            };
+            entries.insert(
+                name.clone(),
+                ListingEntry {
+                    name,
+                    is_dir: metadata.is_dir(),
+                    size: if metadata.is_dir() { 0 } else { metadata.len() },
+                    modified: metadata.modified().ok(),
+                },
+            );
+        }
+    }
+    Ok(entries.into_values().collect())
+}
+
+/// Sort entries with directories first, then by the column of `sort`, then by name.
+pub(crate) fn sort_entries(entries: &mut [ListingEntry], sort: ListingSort) {
+    entries.sort_by(|a, b| {
+        let by_key = match sort.key {
+            SortKey::Name => Ordering::Equal,
+            SortKey::Size => a.size.cmp(&b.size),
+            SortKey::Modified => a.modified.cmp(&b.modified),
+        }
+        .then_with(|| a.name.cmp(&b.name));
+        let by_key = if sort.descending {
+            by_key.reverse()
+        } else {
+            by_key
+        };
+        b.is_dir.cmp(&a.is_dir).then(by_key)
+    });
+}
+
+/// Render the listing of `uri_path` as the breadcrumbs and the table of the index page.
+pub(crate) fn render_listing(
+    uri_path: &str,
+    entries: &[ListingEntry],
+    sort: ListingSort,
+) -> (String, String) {
+    let mut rows = Vec::with_capacity(entries.len() + 1);
+    if uri_path != "/" {
+        rows.push(
+            r#"<tr><td><a href="..">&#x2934;&#xFE0F; ..</a></td><td></td><td></td></tr>"#
+                .to_string(),
+        );
+    }
+    for entry in entries {
+        let trailing = if entry.is_dir { "/" } else { "" };
+        let href = utf8_percent_encode(&entry.name, PATH_SEGMENT_ENCODE_SET);
+        let label = escape_html(&format!("{}{trailing}", entry.name));
+        let size = match entry.is_dir {
+            true => "-".to_string(),
+            false => format_size(entry.size),
+        };
+        let modified = entry.modified.map(http_date).unwrap_or_default();
+        rows.push(format!(
+            "<tr><td><a href=\"{href}{trailing}\">{} {label}</a></td>\
+             <td data-bytes=\"{}\">{size}</td><td>{modified}</td></tr>",
+            icon(entry),
+            entry.size,
+        ));
+    }
+
+    let headers = SortKey::ALL
+        .into_iter()
+        .map(|key| {
+            let is_current = key == sort.key;
+            // Clicking the current column reverses its order.
+            let order = if is_current && !sort.descending {
+                "desc"
+            } else {
+                "asc"
+            };
+            let arrow = match (is_current, sort.descending) {
+                (false, _) => "",
+                (true, false) => " &#x25B2;",
+                (true, true) => " &#x25BC;",
+            };
+            format!(
+                "<th><a href=\"?sort={}&amp;order={order}\">{}</a>{arrow}</th>",
+                key.as_str(),
+                key.label()
+            )
+        })
+        .collect::<String>();
+    let table = format!(
+        "<table>\n<thead><tr>{headers}</tr></thead>\n<tbody>\n{}\n</tbody>\n</table>",
+        rows.join("\n")
+    );
+    (breadcrumbs(uri_path), table)
+}
+
+/// Render the listing of `uri_path` as JSON, with the entries of subdirectories up to the
+/// depth of `json` following their directory.
+///
+/// This does blocking IO, so it should be run with [tokio::task::spawn_blocking].
+pub(crate) fn render_json_listing(
+    uri_path: &str,
+    layers: &[PathBuf],
+    relative_dir: &Path,
+    auto_ignore: bool,
+    sort: ListingSort,
+    json: JsonListing,
+) -> std::io::Result<String> {
+    let walk = JsonWalk {
+        layers,
+        auto_ignore,
+        sort,
+    };
+    let mut entries = Vec::new();
+    walk.collect(relative_dir, uri_path, "", json.depth, &mut entries)?;
+    Ok(serde_json::json!({ "path": uri_path, "entries": entries }).to_string())
+}
+
+struct JsonWalk<'a> {
+    layers: &'a [PathBuf],
+    auto_ignore: bool,
+    sort: ListingSort,
+}
+
+impl JsonWalk<'_> {
+    fn collect(
+        &self,
+        relative_dir: &Path,
+        url_prefix: &str,
+        path_prefix: &str,
+        depth: usize,
+        values: &mut Vec<serde_json::Value>,
+    ) -> std::io::Result<()> {
+        let mut entries = read_entries(self.layers, relative_dir, self.auto_ignore)?;
+        sort_entries(&mut entries, self.sort);
+        for entry in entries {
+            let trailing = if entry.is_dir { "/" } else { "" };
+            let path = format!("{path_prefix}{}{trailing}", entry.name);
+            let url = format!(
+                "{url_prefix}{}{trailing}",
+                utf8_percent_encode(&entry.name, PATH_SEGMENT_ENCODE_SET)
+            );
+            values.push(serde_json::json!({
+                "name": entry.name,
+                "path": path,
+                "type": if entry.is_dir { "directory" } else { "file" },
+                "size": (!entry.is_dir).then_some(entry.size),
+                "mtime": entry.modified.and_then(unix_secs),
+                "url": url,
+            }));
+            if entry.is_dir && depth > 1 {
+                let relative_dir = relative_dir.join(&entry.name);
+                self.collect(&relative_dir, &url, &path, depth - 1, values)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Link each segment of `uri_path` to its directory.
+fn breadcrumbs(uri_path: &str) -> String {
+    let mut href = String::from("/");
+    let mut links = vec![r#"<a href="/">/</a>"#.to_string()];
+    for segment in uri_path.split('/').filter(|s| !s.is_empty()) {
+        let name = percent_decode_str(segment).decode_utf8_lossy();
+        href.push_str(&utf8_percent_encode(&name, PATH_SEGMENT_ENCODE_SET).to_string());
+        href.push('/');
+        links.push(format!("<a href=\"{href}\">{}</a>/", escape_html(&name)));
+    }
+    links.join("")
+}
+
+fn icon(entry: &ListingEntry) -> &'static str {
+    if entry.is_dir {
+        return "&#x1F4C1;";
+    }
+    let mime = mime_guess::from_path(&entry.name).first_or_octet_stream();
+    match mime.type_() {
+        mime::IMAGE => "&#x1F5BC;&#xFE0F;",
+        mime::AUDIO => "&#x1F3B5;",
+        mime::VIDEO => "&#x1F39E;&#xFE0F;",
+        mime::TEXT => "&#x1F4DD;",
+        _ => "&#x1F4C4;",
+    }
+}
+
+/// Format a size in bytes with binary prefixes, like `1.5 KiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{bytes} B"),
+        _ => format!("{size:.1} {}", UNITS[unit]),
+    }
+}
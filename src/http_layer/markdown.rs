@@ -0,0 +1,94 @@
+use std::{path::Path, sync::LazyLock};
+
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd, html};
+use syntect::{
+    highlighting::{Theme, ThemeSet},
+    html::highlighted_html_for_string,
+    parsing::SyntaxSet,
+};
+
+use super::html::escape_html;
+
+static SYNTAXES: LazyLock<SyntaxSet> = LazyLock::new(SyntaxSet::load_defaults_newlines);
+
+static THEME: LazyLock<Theme> = LazyLock::new(|| {
+    ThemeSet::load_defaults()
+        .themes
+        .remove("InspiredGitHub")
+        .unwrap_or_default()
+});
+
+/// Whether `path` is a Markdown file, like `README.md`.
+pub(crate) fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            extension.eq_ignore_ascii_case("md") || extension.eq_ignore_ascii_case("markdown")
+        })
+}
+
+/// Render a Markdown document with the GitHub extensions to an HTML page titled `title`.
+///
+/// Fenced code blocks of known languages are highlighted. This is CPU intensive, so it
+/// should be run with [tokio::task::spawn_blocking].
+pub(crate) fn render_markdown(markdown: &str, title: &str) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_GFM;
+    let mut events = Vec::new();
+    // The events of the current code block, kept as they are if it cannot be highlighted
+    let mut code_block: Option<(String, Vec<Event>)> = None;
+    for event in Parser::new_ext(markdown, options) {
+        match (&mut code_block, event) {
+            (None, Event::Start(Tag::CodeBlock(kind))) => {
+                let language = match &kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split([' ', ',', '{']).next().unwrap_or_default()
+                    }
+                    CodeBlockKind::Indented => "",
+                };
+                code_block = Some((
+                    language.to_string(),
+                    vec![Event::Start(Tag::CodeBlock(kind))],
+                ));
+            }
+            (Some((language, block_events)), Event::End(TagEnd::CodeBlock)) => {
+                let code = block_events
+                    .iter()
+                    .filter_map(|event| match event {
+                        Event::Text(text) => Some(text.as_ref()),
+                        _ => None,
+                    })
+                    .collect::<String>();
+                match highlight(language, &code) {
+                    Some(highlighted) => events.push(Event::Html(highlighted.into())),
+                    None => {
+                        events.append(block_events);
+                        events.push(Event::End(TagEnd::CodeBlock));
+                    }
+                }
+                code_block = None;
+            }
+            (Some((_, block_events)), event) => block_events.push(event),
+            (None, event) => events.push(event),
+        }
+    }
+
+    let mut body = String::with_capacity(markdown.len() * 3 / 2);
+    html::push_html(&mut body, events.into_iter());
+    format!(
+        include_str!("../templates/markdown.html"),
+        escape_html(title),
+        body
+    )
+}
+
+fn highlight(language: &str, code: &str) -> Option<String> {
+    if language.is_empty() {
+        return None;
+    }
+    let syntax = SYNTAXES.find_syntax_by_token(language)?;
+    highlighted_html_for_string(code, &SYNTAXES, syntax, &THEME).ok()
+}
@@ -1,3 +1,21 @@
+pub(crate) mod access_log;
+pub(crate) mod auth;
+pub(crate) mod charset;
+pub(crate) mod compression;
+pub(crate) mod conditional;
+pub(crate) mod content_type;
+pub(crate) mod csp;
+pub(crate) mod headers;
+pub(crate) mod host;
+pub(crate) mod html;
+pub(crate) mod include;
+pub(crate) mod inject;
 pub(crate) mod listener;
+pub(crate) mod listing;
+pub(crate) mod markdown;
+pub(crate) mod proxy;
+pub(crate) mod range;
 pub(crate) mod server;
 pub(crate) mod template;
+pub(crate) mod templating;
+pub(crate) mod tls;
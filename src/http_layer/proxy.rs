@@ -0,0 +1,252 @@
+use std::{collections::HashSet, str::FromStr, sync::Arc};
+
+use axum::{
+    Router,
+    body::Body,
+    extract::Request,
+    http::{HeaderMap, HeaderName, HeaderValue, StatusCode, Uri, header},
+    response::{IntoResponse, Response},
+    routing::any,
+};
+use hyper_util::{
+    client::legacy::{Client, connect::HttpConnector},
+    rt::TokioExecutor,
+};
+
+/// Headers that only apply to a single connection and must not be forwarded.
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    header::CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    header::PROXY_AUTHENTICATE,
+    header::PROXY_AUTHORIZATION,
+    header::TE,
+    header::TRAILER,
+    header::TRANSFER_ENCODING,
+    header::UPGRADE,
+];
+
+/// URL paths served by live-server itself, which cannot be proxied.
+const RESERVED_PREFIXES: [&str; 2] = ["/live-server-ws", "/live-server"];
+
+/// Forward requests below a URL path prefix to an upstream server.
+///
+/// The request path is appended to the upstream URL unchanged, so with
+/// `/api=http://127.0.0.1:3000`, a request to `/api/users?page=2` is forwarded to
+/// `http://127.0.0.1:3000/api/users?page=2`.
+///
+/// ```
+/// use live_server::Proxy;
+///
+/// let proxy: Proxy = "/api=http://127.0.0.1:3000".parse().unwrap();
+/// assert_eq!(proxy.prefix, "/api");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Proxy {
+    /// URL path prefix to match, like `/api`
+    pub prefix: String,
+    /// Upstream server to forward the requests to, like `http://127.0.0.1:3000`
+    pub upstream: Uri,
+}
+
+impl FromStr for Proxy {
+    type Err = String;
+
+    /// Parse a proxy route in the form of `PREFIX=UPSTREAM`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((prefix, upstream)) = s.split_once('=') else {
+            return Err(format!(
+                "Invalid proxy `{s}`, expected the form of `/api=http://127.0.0.1:3000`"
+            ));
+        };
+        let prefix = prefix.trim().trim_end_matches('/');
+        if !prefix.starts_with('/') {
+            return Err(format!(
+                "Proxy prefix `{prefix}` must start with `/` and must not be the root"
+            ));
+        }
+        let upstream = upstream
+            .trim()
+            .parse::<Uri>()
+            .map_err(|err| format!("Invalid proxy upstream `{upstream}`: {err}"))?;
+        if upstream.scheme_str() != Some("http") || upstream.authority().is_none() {
+            return Err(format!(
+                "Invalid proxy upstream `{upstream}`, only `http://` URLs are supported"
+            ));
+        }
+        Ok(Self {
+            prefix: prefix.to_string(),
+            upstream,
+        })
+    }
+}
+
+impl Proxy {
+    /// The origin of the upstream, like `http://127.0.0.1:3000`.
+    fn origin(&self) -> String {
+        match (self.upstream.scheme_str(), self.upstream.authority()) {
+            (Some(scheme), Some(authority)) => format!("{scheme}://{authority}"),
+            _ => String::new(),
+        }
+    }
+
+    /// The path of the upstream without trailing slashes, like `/v1`, which may be empty.
+    fn base_path(&self) -> &str {
+        self.upstream.path().trim_end_matches('/')
+    }
+}
+
+/// Check that every proxy has its own prefix, which is not used by live-server itself,
+/// because the routes of the same path cannot be merged.
+pub(crate) fn check_proxy_prefixes(proxies: &[Proxy]) -> Result<(), String> {
+    let mut prefixes = HashSet::new();
+    for proxy in proxies {
+        let prefix = proxy.prefix.trim_end_matches('/');
+        let is_reserved = RESERVED_PREFIXES.iter().any(|reserved| {
+            prefix == *reserved
+                || prefix
+                    .strip_prefix(reserved)
+                    .is_some_and(|rest| rest.starts_with('/'))
+        });
+        if is_reserved {
+            return Err(format!(
+                "Proxy prefix `{prefix}` is reserved by live-server"
+            ));
+        }
+        if !prefixes.insert(prefix) {
+            return Err(format!("Proxy prefix `{prefix}` is used more than once"));
+        }
+    }
+    Ok(())
+}
+
+/// Add the routes of all proxies to `router`.
+///
+/// If live-server requires authentication, `Authorization` holds its credentials, which are
+/// not forwarded with `strip_authorization`.
+pub(crate) fn add_proxy_routes<S>(
+    mut router: Router<S>,
+    proxies: &[Proxy],
+    https: bool,
+    strip_authorization: bool,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let client = Client::builder(TokioExecutor::new()).build_http::<Body>();
+    for proxy in proxies {
+        let prefix = proxy.prefix.clone();
+        let proxy = Arc::new(proxy.clone());
+        let client = client.clone();
+        let handler = any(move |req: Request| {
+            forward(
+                client.clone(),
+                proxy.clone(),
+                https,
+                strip_authorization,
+                req,
+            )
+        });
+        router = router
+            .route(&prefix, handler.clone())
+            .route(&format!("{prefix}/"), handler.clone())
+            .route(&format!("{prefix}/{{*path}}"), handler);
+    }
+    router
+}
+
+async fn forward(
+    client: Client<HttpConnector, Body>,
+    proxy: Arc<Proxy>,
+    https: bool,
+    strip_authorization: bool,
+    req: Request,
+) -> Response {
+    let (mut parts, body) = req.into_parts();
+    let path_and_query = parts
+        .uri
+        .path_and_query()
+        .map(|p| p.as_str())
+        .unwrap_or("/");
+    let target = format!("{}{}{path_and_query}", proxy.origin(), proxy.base_path());
+    let uri = match target.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(err) => {
+            log::error!("Failed to build proxy URL `{target}`: {err}");
+            return (StatusCode::BAD_GATEWAY, err.to_string()).into_response();
+        }
+    };
+
+    let original_host = parts.headers.remove(header::HOST);
+    remove_hop_by_hop_headers(&mut parts.headers);
+    if strip_authorization {
+        parts.headers.remove(header::AUTHORIZATION);
+    }
+    if let Some(host) = original_host {
+        parts.headers.insert("x-forwarded-host", host);
+    }
+    let proto = if https { "https" } else { "http" };
+    parts
+        .headers
+        .insert("x-forwarded-proto", HeaderValue::from_static(proto));
+    parts.uri = uri;
+    // The client sets `Host` of the upstream according to the URI.
+    let upstream_req = Request::from_parts(parts, body);
+
+    match client.request(upstream_req).await {
+        Ok(response) => {
+            let (mut parts, body) = response.into_parts();
+            remove_hop_by_hop_headers(&mut parts.headers);
+            rewrite_location(&mut parts.headers, &proxy);
+            Response::from_parts(parts, Body::new(body))
+        }
+        Err(err) => {
+            log::error!("Failed to proxy request to {target}: {err}");
+            (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to proxy request to {target}: {err}"),
+            )
+                .into_response()
+        }
+    }
+}
+
+fn remove_hop_by_hop_headers(headers: &mut HeaderMap) {
+    // `Connection` may list further hop-by-hop headers.
+    let listed = headers
+        .get_all(header::CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|name| HeaderName::from_str(name.trim()).ok())
+        .collect::<Vec<_>>();
+    for name in listed.iter().chain(HOP_BY_HOP_HEADERS.iter()) {
+        headers.remove(name);
+    }
+}
+
+/// Turn redirects to the upstream into redirects to the same path on live-server.
+fn rewrite_location(headers: &mut HeaderMap, proxy: &Proxy) {
+    let Some(location) = headers.get(header::LOCATION).and_then(|v| v.to_str().ok()) else {
+        return;
+    };
+    let Some(path) = strip_path_prefix(location, &proxy.origin())
+        .map(|path| strip_path_prefix(path, proxy.base_path()).unwrap_or(path))
+    else {
+        return;
+    };
+    let path = if path.starts_with('/') {
+        path.to_string()
+    } else {
+        format!("/{path}")
+    };
+    if let Ok(value) = HeaderValue::from_str(&path) {
+        headers.insert(header::LOCATION, value);
+    }
+}
+
+/// Strip `prefix` from `url` only at a path boundary, so that `http://127.0.0.1:3000` is
+/// not a prefix of `http://127.0.0.1:30001` and `/v1` is not a prefix of `/v10`.
+fn strip_path_prefix<'a>(url: &'a str, prefix: &str) -> Option<&'a str> {
+    url.strip_prefix(prefix)
+        .filter(|rest| rest.is_empty() || rest.starts_with(['/', '?']))
+}
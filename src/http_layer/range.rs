@@ -0,0 +1,191 @@
+use std::{
+    io::{self, SeekFrom},
+    ops::RangeInclusive,
+    sync::Arc,
+    time::SystemTime,
+};
+
+use axum::{
+    body::{Body, Bytes},
+    http::{HeaderMap, header},
+};
+use futures::{StreamExt, TryStreamExt, future, stream};
+use tokio::{
+    fs::File,
+    io::{AsyncReadExt, AsyncSeekExt, Take},
+};
+use tokio_util::io::ReaderStream;
+
+use crate::http_layer::conditional::{etag_list_matches, unix_secs};
+
+/// Headers with more ranges than this are ignored, so that a request cannot make the
+/// response many times larger than the file.
+const MAX_RANGES: usize = 16;
+
+/// The result of evaluating a `Range` request header against a file of known length.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ByteRanges {
+    /// The header is absent, malformed or uses a unit other than `bytes`,
+    /// so the whole file should be sent with `200 OK`.
+    Full,
+    /// At least one range is satisfiable and should be sent with `206 Partial Content`.
+    Partial(Vec<RangeInclusive<u64>>),
+    /// None of the ranges overlap the file, which should be answered with
+    /// `416 Range Not Satisfiable`.
+    Unsatisfiable,
+}
+
+/// Parse a `Range` header value such as `bytes=0-99,200-,-50` for a file of `len` bytes.
+///
+/// Unsatisfiable ranges are dropped; only if all of them are unsatisfiable is the whole
+/// header considered unsatisfiable. Overlapping and adjacent ranges are merged, and headers
+/// with more than [MAX_RANGES] ranges are ignored.
+pub(crate) fn parse_range(value: &str, len: u64) -> ByteRanges {
+    let Some(specs) = value.trim().strip_prefix("bytes=") else {
+        return ByteRanges::Full;
+    };
+    let specs = specs
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>();
+    if specs.len() > MAX_RANGES {
+        return ByteRanges::Full;
+    }
+
+    let mut ranges: Vec<RangeInclusive<u64>> = Vec::new();
+    for spec in specs {
+        let Some((start, end)) = spec.split_once('-') else {
+            return ByteRanges::Full;
+        };
+        let (start, end) = (start.trim(), end.trim());
+        let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+            // `first-last`
+            (Ok(first), Ok(last)) if first <= last => {
+                (first < len).then(|| first..=last.min(len - 1))
+            }
+            // `first-`
+            (Ok(first), Err(_)) if end.is_empty() => (first < len).then(|| first..=len - 1),
+            // `-suffix`
+            (Err(_), Ok(suffix)) if start.is_empty() => {
+                (suffix > 0 && len > 0).then(|| len.saturating_sub(suffix)..=len - 1)
+            }
+            _ => return ByteRanges::Full,
+        };
+        ranges.extend(range);
+    }
+
+    if ranges.is_empty() {
+        return ByteRanges::Unsatisfiable;
+    }
+    ranges.sort_by_key(|range| *range.start());
+    let mut merged: Vec<RangeInclusive<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if *range.start() <= last.end() + 1 => {
+                *last = *last.start()..=*last.end().max(range.end());
+            }
+            _ => merged.push(range),
+        }
+    }
+    ByteRanges::Partial(merged)
+}
+
+/// Evaluate the `Range` and `If-Range` request headers.
+///
+/// If `If-Range` is present but matches neither the current `etag` nor the `modified`
+/// time of the file, the client holds a stale copy and must receive the full file instead
+/// of a part of it.
+pub(crate) fn requested_ranges(
+    headers: &HeaderMap,
+    len: u64,
+    etag: &str,
+    modified: Option<SystemTime>,
+) -> ByteRanges {
+    let Some(range) = headers.get(header::RANGE).and_then(|v| v.to_str().ok()) else {
+        return ByteRanges::Full;
+    };
+    if let Some(if_range) = headers.get(header::IF_RANGE) {
+        let if_range = if_range.to_str().unwrap_or_default().trim();
+        let is_fresh = if if_range.starts_with('"') || if_range.starts_with("W/") {
+            etag_list_matches(if_range, etag, true)
+        } else {
+            httpdate::parse_http_date(if_range)
+                .ok()
+                .zip(modified)
+                .is_some_and(|(date, modified)| {
+                    unix_secs(date).is_some() && unix_secs(date) == unix_secs(modified)
+                })
+        };
+        if !is_fresh {
+            return ByteRanges::Full;
+        }
+    }
+    parse_range(range, len)
+}
+
+/// Format the value of a `Content-Range` header for one part of a file.
+pub(crate) fn content_range(range: &RangeInclusive<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{len}", range.start(), range.end())
+}
+
+/// Stream one part of a file.
+pub(crate) async fn file_part(
+    mut file: File,
+    range: &RangeInclusive<u64>,
+) -> io::Result<ReaderStream<Take<File>>> {
+    file.seek(SeekFrom::Start(*range.start())).await?;
+    Ok(ReaderStream::new(
+        file.take(range.end() - range.start() + 1),
+    ))
+}
+
+/// Build a streaming `multipart/byteranges` body from the requested parts of `file`,
+/// returning it together with its exact length.
+///
+/// Every part is read from a clone of the same handle, so all parts come from the file
+/// whose length was measured even if it is replaced while the response is sent. The
+/// parts are read one after another, so sharing the cursor is fine.
+pub(crate) fn multipart_body(
+    file: File,
+    len: u64,
+    ranges: Vec<RangeInclusive<u64>>,
+    content_type: &str,
+    boundary: &str,
+) -> (u64, Body) {
+    let closing = format!("--{boundary}--\r\n");
+    let mut content_length = closing.len() as u64;
+    let file = Arc::new(file);
+    let mut parts = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        let head = format!(
+            "--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {}\r\n\r\n",
+            content_range(&range, len)
+        );
+        content_length += head.len() as u64 + (range.end() - range.start() + 1) + 2;
+        let file = file.clone();
+        let data = stream::once(async move {
+            let file = file.try_clone().await?;
+            file_part(file, &range).await
+        })
+        .try_flatten();
+        parts.push(
+            stream::once(future::ready(Ok(Bytes::from(head))))
+                .chain(data)
+                .chain(stream::once(future::ready(Ok(Bytes::from_static(b"\r\n"))))),
+        );
+    }
+    let body = stream::iter(parts)
+        .flatten()
+        .chain(stream::once(future::ready(Ok(Bytes::from(closing)))));
+    (content_length, Body::from_stream(body))
+}
+
+/// Generate a boundary that is unlikely to appear in the served file.
+pub(crate) fn multipart_boundary() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("live-server-{nanos:x}")
+}
@@ -5,22 +5,60 @@ use axum::{
         Request, State, WebSocketUpgrade,
         ws::{Message, Utf8Bytes, WebSocket},
     },
-    http::{HeaderMap, HeaderValue, StatusCode, header},
+    http::{HeaderMap, HeaderValue, StatusCode, Uri, header},
+    middleware,
+    response::{IntoResponse, Response},
     routing::get,
 };
 use futures::{sink::SinkExt, stream::StreamExt};
-use mime_guess::mime;
-use percent_encoding::{AsciiSet, CONTROLS, percent_decode_str, utf8_percent_encode};
+use globset::GlobSet;
+use mime_guess::{Mime, mime};
+use percent_encoding::percent_decode_str;
 use std::{
-    fs,
     io::ErrorKind,
     path::{Component, Path, PathBuf},
     sync::Arc,
 };
-use tokio::{net::TcpListener, sync::broadcast};
+use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt},
+    net::TcpListener,
+    sync::broadcast,
+};
+use tokio_rustls::TlsAcceptor;
+use tokio_util::io::ReaderStream;
 
 use crate::{
-    http_layer::template::{error_html, index_html},
+    file_layer::{
+        mount::{Mount, find_root, resolve_mount},
+        overlay::resolve_in_layers,
+    },
+    http_layer::{
+        access_log::{AccessLog, AccessLogFormat, ClientAddr, log_access},
+        auth::{Authenticator, BasicAuth, require_auth},
+        charset::{detect_charset, is_utf8_label, utf16_to_utf8},
+        compression::{accepted_encodings, append_vary, compress, open_precompressed},
+        conditional::{content_etag, file_etag, http_date, is_not_modified},
+        content_type::{MimeOverride, MimeTypes, SNIFF_LENGTH, is_html, sniff},
+        headers::{HeaderRule, add_custom_headers, compile_header_rules},
+        host::{check_host, is_allowed_origin},
+        html::escape_html,
+        include::{IncludedFiles, resolve_includes},
+        inject::{Injection, ScriptPosition, inject_html},
+        listing::{
+            JsonListing, ListingSort, read_entries, render_json_listing, render_listing,
+            sort_entries,
+        },
+        markdown::{is_markdown, render_markdown},
+        proxy::{Proxy, add_proxy_routes},
+        range::{
+            ByteRanges, content_range, file_part, multipart_body, multipart_boundary,
+            requested_ranges,
+        },
+        template::{error_html, index_html},
+        templating::{render_template, template_name},
+        tls::TlsListener,
+    },
     utils::is_ignored,
 };
 
@@ -31,37 +69,85 @@ const WEBSOCKET_FUNCTION: &str = include_str!("../templates/websocket.js");
 /// knows it's a successful reload.
 const RELOAD_PAYLOAD: &str = include_str!("../templates/reload.js");
 
-/// Characters that must be escaped when a file name is used as one URL path segment.
-const PATH_SEGMENT_ENCODE_SET: &AsciiSet = &CONTROLS
-    .add(b' ')
-    .add(b'"')
-    .add(b'#')
-    .add(b'%')
-    .add(b'&')
-    .add(b'/')
-    .add(b'<')
-    .add(b'>')
-    .add(b'?')
-    .add(b'[')
-    .add(b'\\')
-    .add(b']')
-    .add(b'^')
-    .add(b'`')
-    .add(b'{')
-    .add(b'|')
-    .add(b'}');
-
-pub(crate) async fn serve(tcp_listener: TcpListener, router: Router) {
-    axum::serve(tcp_listener, router).await.unwrap();
+/// Reserved URL of the client script, which is served as a file rather than inline so
+/// that it works with a strict `Content-Security-Policy`.
+const CLIENT_SCRIPT_PATH: &str = "/live-server/client.js";
+
+/// Request headers deciding whether the client script is injected.
+const INJECTION_VARY: &str = "Sec-Fetch-Dest, HX-Request, Turbo-Frame";
+
+/// Reserved URL of the reload payload.
+const RELOAD_SCRIPT_PATH: &str = "/live-server/reload.js";
+
+pub(crate) async fn serve(tcp_listener: TcpListener, tls: Option<TlsAcceptor>, router: Router) {
+    // The client addresses are used by the access log.
+    let service = router.into_make_service_with_connect_info::<ClientAddr>();
+    match tls {
+        Some(acceptor) => {
+            let listener = TlsListener::new(tcp_listener, acceptor);
+            axum::serve(listener, service).await.unwrap();
+        }
+        None => axum::serve(tcp_listener, service).await.unwrap(),
+    }
 }
 
 pub struct Options {
     /// Always hard reload the page instead of hot-reload
     pub hard_reload: bool,
-    /// Show page list of the current URL if `index.html` does not exist
+    /// Show page list of the current URL if `index.html` does not exist, and list
+    /// directories as JSON for requests accepting `application/json` or with `?format=json`
     pub index_listing: bool,
     /// Ignore hidden and ignored files
     pub auto_ignore: bool,
+    /// Compress responses on the fly according to `Accept-Encoding`
+    pub compression: bool,
+    /// Serve precompressed sibling files like `foo.js.br` or `foo.js.gz` if they exist
+    pub precompressed: bool,
+    /// Serve this file (relative to the root) for HTML requests to paths that do not exist,
+    /// so that client-side routers of single-page applications work
+    pub spa: Option<PathBuf>,
+    /// Forward requests below these URL path prefixes to upstream servers
+    pub proxies: Vec<Proxy>,
+    /// Add these headers to static files, listings and error pages
+    pub headers: Vec<HeaderRule>,
+    /// Resolve extensionless paths like `/about` to `.html` files like `about.html`
+    pub clean_urls: bool,
+    /// Redirect `/about.html` to `/about` if `clean_urls` is enabled
+    pub redirect_html_extension: bool,
+    /// Serve additional directories under URL path prefixes, and reload on their changes
+    pub mounts: Vec<Mount>,
+    /// Fall through these directories in order for files that do not exist in the root,
+    /// and reload on their changes
+    pub overlays: Vec<PathBuf>,
+    /// Require these credentials with HTTP Basic authentication for all routes
+    pub auth: Option<BasicAuth>,
+    /// Accept these host names in addition to IP addresses and `localhost`, like
+    /// `example.test`, or `.example.test` for its subdomains too, or `*` for all
+    pub allowed_hosts: Vec<String>,
+    /// Inject the reload script before `</body>` or `</head>` of HTML documents
+    pub script_position: ScriptPosition,
+    /// Never inject the reload script into HTML files whose URL paths match these globs,
+    /// like `/emails/**`
+    pub no_inject: Vec<String>,
+    /// Render Markdown files as HTML pages, and serve `README.md` for directories without
+    /// `index.html`
+    pub markdown: bool,
+    /// Replace `<!--#include file="..." -->` directives in HTML files with the files they
+    /// refer to, and reload the pages when those change
+    pub includes: bool,
+    /// Render HTML files as templates with the data files in `data/` and the environment
+    /// variables, and reload the pages when those change
+    pub templates: bool,
+    /// Serve files with these extensions with these MIME types instead of the built-in ones
+    pub mime_types: Vec<MimeOverride>,
+    /// MIME type of files with unknown extensions
+    pub default_mime: Mime,
+    /// Detect the MIME type of files without an extension from their content
+    pub sniff_mime: bool,
+    /// Log every request in this format
+    pub access_log: Option<AccessLogFormat>,
+    /// Append the access log to this file instead of writing it to stderr
+    pub access_log_file: Option<PathBuf>,
 }
 
 pub(crate) struct AppState {
@@ -71,8 +157,50 @@ pub(crate) struct AppState {
     pub(crate) index_listing: bool,
     /// Ignore hidden and ignored files
     pub(crate) auto_ignore: bool,
+    /// Compress responses on the fly according to `Accept-Encoding`
+    pub(crate) compression: bool,
+    /// Serve precompressed sibling files like `foo.js.br` or `foo.js.gz` if they exist
+    pub(crate) precompressed: bool,
+    /// Fallback file of single-page applications, relative to the root
+    pub(crate) spa: Option<PathBuf>,
+    /// Directories served under URL path prefixes, with absolute paths
+    pub(crate) mounts: Vec<Mount>,
+    /// Routes forwarded to upstream servers
+    pub(crate) proxies: Vec<Proxy>,
+    /// Whether the server is served over HTTPS
+    pub(crate) https: bool,
+    /// Custom headers added to static files, listings and error pages
+    pub(crate) headers: Vec<HeaderRule>,
+    /// Resolve extensionless paths like `/about` to `.html` files like `about.html`
+    pub(crate) clean_urls: bool,
+    /// Redirect `/about.html` to `/about` if `clean_urls` is enabled
+    pub(crate) redirect_html_extension: bool,
+    /// Require these credentials with HTTP Basic authentication for all routes
+    pub(crate) auth: Option<BasicAuth>,
+    /// Host names accepted in addition to IP addresses and `localhost`
+    pub(crate) allowed_hosts: Vec<String>,
+    /// Inject the reload script before `</body>` or `</head>` of HTML documents
+    pub(crate) script_position: ScriptPosition,
+    /// URL paths of HTML files that never get the reload script
+    pub(crate) no_inject: GlobSet,
+    /// Render Markdown files as HTML pages, and serve `README.md` for directories without
+    /// `index.html`
+    pub(crate) markdown: bool,
+    /// Replace `<!--#include file="..." -->` directives in HTML files with the files they
+    /// refer to
+    pub(crate) includes: bool,
+    /// Render HTML files as templates with the data files in `data/` and the environment
+    /// variables
+    pub(crate) templates: bool,
+    /// MIME types of files by their extensions or content
+    pub(crate) mime_types: MimeTypes,
+    /// Files included by pages, which are watched even if ignored
+    pub(crate) included_files: IncludedFiles,
+    /// Where requests are logged, if anywhere
+    pub(crate) access_log: Option<Arc<AccessLog>>,
     pub(crate) tx: Arc<broadcast::Sender<()>>,
-    pub(crate) root: PathBuf,
+    /// Root directories sharing the URL space, searched in order, with absolute paths
+    pub(crate) roots: Vec<PathBuf>,
 }
 
 impl Default for Options {
@@ -81,25 +209,97 @@ impl Default for Options {
             hard_reload: false,
             index_listing: true,
             auto_ignore: false,
+            compression: false,
+            precompressed: false,
+            spa: None,
+            proxies: Vec::new(),
+            headers: Vec::new(),
+            clean_urls: false,
+            redirect_html_extension: false,
+            mounts: Vec::new(),
+            overlays: Vec::new(),
+            auth: None,
+            allowed_hosts: Vec::new(),
+            script_position: ScriptPosition::default(),
+            no_inject: Vec::new(),
+            markdown: false,
+            includes: false,
+            templates: false,
+            mime_types: Vec::new(),
+            default_mime: mime::TEXT_PLAIN,
+            sniff_mime: false,
+            access_log: None,
+            access_log_file: None,
         }
     }
 }
 
 pub(crate) fn create_server(state: AppState) -> Router {
-    let tx = state.tx.clone();
-    Router::new()
+    let state = Arc::new(state);
+    let header_rules = Arc::new(compile_header_rules(&state.headers));
+    let router = Router::new()
         .route("/", get(static_assets))
         .route("/{*path}", get(static_assets))
+        .route_layer(middleware::from_fn(move |req, next| {
+            add_custom_headers(header_rules.clone(), req, next)
+        }))
+        .route("/live-server-ws", get(websocket))
         .route(
-            "/live-server-ws",
-            get(|ws: WebSocketUpgrade| async move {
-                ws.on_failed_upgrade(|error| {
-                    log::error!("Failed to upgrade websocket: {error}");
-                })
-                .on_upgrade(|socket: WebSocket| on_websocket_upgrade(socket, tx))
+            CLIENT_SCRIPT_PATH,
+            get(|| async {
+                // The script tag tells whether to hard reload.
+                javascript(format!(
+                    r#"{WEBSOCKET_FUNCTION}(document.currentScript.dataset.hard === "true")"#
+                ))
             }),
         )
-        .with_state(Arc::new(state))
+        .route(
+            RELOAD_SCRIPT_PATH,
+            get(|| async { javascript(RELOAD_PAYLOAD.to_string()) }),
+        );
+    let mut router = add_proxy_routes(router, &state.proxies, state.https, state.auth.is_some())
+        .layer(middleware::from_fn_with_state(state.clone(), compress));
+    if let Some(auth) = &state.auth {
+        let authenticator = Arc::new(Authenticator::new(auth.clone()));
+        router = router.layer(middleware::from_fn(move |req, next| {
+            require_auth(authenticator.clone(), req, next)
+        }));
+    }
+    router = router.layer(middleware::from_fn_with_state(state.clone(), check_host));
+    if let Some(access_log) = &state.access_log {
+        let access_log = access_log.clone();
+        router = router.layer(middleware::from_fn(move |req, next| {
+            log_access(access_log.clone(), req, next)
+        }));
+    }
+    router.with_state(state)
+}
+
+fn javascript(script: String) -> (HeaderMap, String) {
+    let mut headers = HeaderMap::new();
+    headers.append(
+        header::CONTENT_TYPE,
+        HeaderValue::from_static("text/javascript; charset=utf-8"),
+    );
+    headers.append(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+    (headers, script)
+}
+
+async fn websocket(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    uri: Uri,
+    ws: WebSocketUpgrade,
+) -> Response {
+    // Other websites must not be able to listen to the changes of the files.
+    if !is_allowed_origin(&state.allowed_hosts, &headers, &uri) {
+        return (StatusCode::FORBIDDEN, "Origin is not allowed").into_response();
+    }
+    let tx = state.tx.clone();
+    ws.on_failed_upgrade(|error| {
+        log::error!("Failed to upgrade websocket: {error}");
+    })
+    .on_upgrade(|socket: WebSocket| on_websocket_upgrade(socket, tx))
 }
 
 async fn on_websocket_upgrade(socket: WebSocket, tx: Arc<broadcast::Sender<()>>) {
@@ -121,63 +321,6 @@ async fn on_websocket_upgrade(socket: WebSocket, tx: Arc<broadcast::Sender<()>>)
     };
 }
 
-fn get_index_listing(uri_path: &str, root: &Path, directory: &Path, auto_ignore: bool) -> String {
-    let is_root = uri_path == "/";
-    let entries = fs::read_dir(directory).unwrap();
-    let mut entry_names = entries
-        .into_iter()
-        .filter_map(|e| {
-            if let Ok(entry) = e {
-                if auto_ignore {
-                    match is_ignored(root, &entry.path()) {
-                        Ok(ignored) => {
-                            if ignored {
-                                return None;
-                            }
-                        }
-                        Err(err) => {
-                            log::error!("Failed to check ignore files: {err}");
-                            // Do nothing if we cannot know if it's an ignored entry
-                            return None;
-                        }
-                    }
-                }
-                let is_dir = entry.metadata().ok()?.is_dir();
-                let trailing = if is_dir { "/" } else { "" };
-                entry
-                    .file_name()
-                    .to_str()
-                    .map(|name| format!("{name}{trailing}"))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<String>>();
-    entry_names.sort();
-    if !is_root {
-        entry_names.insert(0, "..".to_string());
-    }
-    entry_names
-        .into_iter()
-        .map(|entry_name| {
-            let (name, trailing) = match entry_name.strip_suffix('/') {
-                Some(name) => (name, "/"),
-                None => (entry_name.as_str(), ""),
-            };
-            let href = utf8_percent_encode(name, PATH_SEGMENT_ENCODE_SET);
-            let label = escape_html(&entry_name);
-            format!("<li><a href=\"{href}{trailing}\">{label}</a></li>")
-        })
-        .collect::<Vec<String>>()
-        .join("\n")
-}
-
-fn escape_html(text: &str) -> String {
-    text.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-}
-
 /// Decode each URL path segment independently and reject anything that would become
 /// an absolute path, a parent path, or multiple filesystem path components.
 fn decode_uri_path(uri_path: &str) -> Result<PathBuf, &'static str> {
@@ -209,85 +352,168 @@ async fn static_assets(
     let uri_path = req.uri().path();
     // Avoid [directory traversal attack](https://en.wikipedia.org/wiki/Directory_traversal_attack).
     if uri_path.starts_with("//") {
-        let redirect = format!("/{}", uri_path.trim_start_matches("/"));
-        let mut headers = HeaderMap::new();
-        headers.append(header::LOCATION, HeaderValue::from_str(&redirect).unwrap());
-        return (StatusCode::TEMPORARY_REDIRECT, headers, Body::empty());
+        return redirect(&format!("/{}", uri_path.trim_start_matches("/")));
     }
-    let relative_path = match decode_uri_path(uri_path) {
+    let injection = injection_for(&state, req.headers(), uri_path);
+    let (mut layers, mount_path) = resolve_mount(&state.mounts, &state.roots, uri_path);
+    let relative_path = match decode_uri_path(mount_path) {
         Ok(path) => path,
         Err(err_msg) => {
-            let body = generate_error_body(err_msg, state.hard_reload, is_reload);
-            return (StatusCode::BAD_REQUEST, HeaderMap::new(), body);
+            return error_page(
+                &state,
+                StatusCode::BAD_REQUEST,
+                err_msg,
+                is_reload,
+                injection,
+            )
+            .await;
         }
     };
-    let requested_path = state.root.join(relative_path);
-    let is_accessing_dir = requested_path.is_dir();
+    let mut requested_path = resolve_in_layers(layers, &relative_path).await;
+    let metadata = fs::metadata(&requested_path).await.ok();
+    let mut is_accessing_dir = metadata.as_ref().is_some_and(|m| m.is_dir());
+    if state.clean_urls {
+        let is_file = metadata.is_some_and(|m| m.is_file());
+        match resolve_clean_url(
+            &state,
+            uri_path,
+            layers,
+            &relative_path,
+            is_file,
+            is_accessing_dir,
+        )
+        .await
+        {
+            CleanUrl::Redirect(location) => {
+                let query = req
+                    .uri()
+                    .query()
+                    .map(|q| format!("?{q}"))
+                    .unwrap_or_default();
+                return redirect(&format!("{location}{query}"));
+            }
+            CleanUrl::File(path) => {
+                requested_path = path;
+                is_accessing_dir = false;
+            }
+            CleanUrl::Unchanged => {}
+        }
+    }
     if is_accessing_dir && !uri_path.ends_with('/') {
         // redirect so parent links work correctly
-        let redirect = format!("{uri_path}/");
-        let mut headers = HeaderMap::new();
-        headers.append(header::LOCATION, HeaderValue::from_str(&redirect).unwrap());
-        return (StatusCode::TEMPORARY_REDIRECT, headers, Body::empty());
+        return redirect(&format!("{uri_path}/"));
+    }
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok());
+    if state.index_listing
+        && is_accessing_dir
+        && let Some(json) = JsonListing::from_request(accept, req.uri().query())
+    {
+        let sort = ListingSort::from_query(req.uri().query());
+        return json_listing(&state, uri_path, layers, relative_path, sort, json).await;
     }
-    let path = if is_accessing_dir {
-        requested_path.join("index.html")
+    let mut path = if is_accessing_dir {
+        resolve_in_layers(layers, &relative_path.join("index.html")).await
     } else {
         requested_path.clone()
     };
-    let mime = mime_guess::from_path(&path).first_or_text_plain();
+    if state.markdown && is_accessing_dir && !fs::try_exists(&path).await.unwrap_or(true) {
+        let readme = resolve_in_layers(layers, &relative_path.join("README.md")).await;
+        if fs::try_exists(&readme).await.unwrap_or(false) {
+            path = readme;
+        }
+    }
+    if let Some(fallback) = &state.spa
+        && !is_accessing_dir
+        && is_history_route(&req, &path)
+        && fs::metadata(&path)
+            .await
+            .is_err_and(|err| err.kind() == ErrorKind::NotFound)
+    {
+        layers = &state.roots;
+        path = resolve_in_layers(layers, fallback).await;
+    }
+    let root = find_root(layers, &path).unwrap_or(&layers[0]);
+    let mut mime = state.mime_types.guess(&path);
 
     let mut headers = HeaderMap::new();
-    let content_type = if mime.type_() == mime::TEXT {
-        format!("{}; charset=utf-8", mime.as_ref())
-    } else {
-        mime.as_ref().to_string()
-    };
-    headers.append(
-        header::CONTENT_TYPE,
-        HeaderValue::from_str(&content_type).unwrap(),
-    );
+    headers.append(header::CONTENT_TYPE, content_type_header(&mime));
 
     if state.auto_ignore {
-        match is_ignored(&state.root, &path) {
+        match is_ignored(root, &path) {
             Ok(ignored) => {
                 if ignored {
                     let err_msg =
                         "Unable to access ignored or hidden file, because `--ignore` is enabled";
-                    let body = generate_error_body(err_msg, state.hard_reload, is_reload);
-
-                    return (StatusCode::FORBIDDEN, HeaderMap::new(), body);
+                    return error_page(
+                        &state,
+                        StatusCode::FORBIDDEN,
+                        err_msg,
+                        is_reload,
+                        injection,
+                    )
+                    .await;
                 }
             }
             Err(err) => {
                 let err_msg = format!("Failed to check ignore files: {err}");
-                let body = generate_error_body(&err_msg, state.hard_reload, is_reload);
                 log::error!("{err_msg}");
-
-                return (StatusCode::INTERNAL_SERVER_ERROR, HeaderMap::new(), body);
+                return error_page(
+                    &state,
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    &err_msg,
+                    is_reload,
+                    injection,
+                )
+                .await;
             }
         }
     }
 
-    // Read the file.
-    let mut file = match fs::read(&path) {
-        Ok(file) => file,
+    // Open the file.
+    let open_result = match File::open(&path).await {
+        Ok(file) => file.metadata().await.map(|metadata| (file, metadata)),
+        Err(err) => Err(err),
+    };
+    let (mut file, mut metadata) = match open_result {
+        Ok(opened) => opened,
         Err(err) => {
             let status_code = match err.kind() {
                 ErrorKind::NotFound => {
                     if state.index_listing && is_accessing_dir {
-                        let script = format_script(state.hard_reload, is_reload, false);
-                        let html = index_html(
-                            uri_path,
-                            &script,
-                            &get_index_listing(
-                                uri_path,
-                                &state.root,
-                                &requested_path,
-                                state.auto_ignore,
-                            ),
-                        );
-                        return (StatusCode::OK, headers, html);
+                        let layers = layers.to_vec();
+                        let auto_ignore = state.auto_ignore;
+                        let listing_path = uri_path.to_string();
+                        let sort = ListingSort::from_query(req.uri().query());
+                        let listing = tokio::task::spawn_blocking(move || {
+                            let mut entries = read_entries(&layers, &relative_path, auto_ignore)?;
+                            sort_entries(&mut entries, sort);
+                            Ok(render_listing(&listing_path, &entries, sort))
+                        })
+                        .await
+                        .unwrap_or_else(|err| Err(std::io::Error::other(err)));
+                        match listing {
+                            Ok((breadcrumbs, table)) => {
+                                let script = format_script(state.hard_reload, is_reload, false);
+                                let html = index_html(uri_path, &script, &breadcrumbs, &table);
+                                headers.append(header::VARY, HeaderValue::from_static("Accept"));
+                                return (StatusCode::OK, headers, html);
+                            }
+                            Err(err) => {
+                                let err_msg = format!("Failed to read directory: {err}");
+                                log::error!("{err_msg}");
+                                return error_page(
+                                    &state,
+                                    StatusCode::INTERNAL_SERVER_ERROR,
+                                    &err_msg,
+                                    is_reload,
+                                    injection,
+                                )
+                                .await;
+                            }
+                        }
                     }
                     StatusCode::NOT_FOUND
                 }
@@ -297,58 +523,458 @@ async fn static_assets(
                 Some(path) => log::warn!("Failed to read \"{path}\": {err}"),
                 None => log::warn!("Failed to read file with invalid path: {err}"),
             }
-            return (
-                status_code,
-                headers,
-                if mime == "text/html" {
-                    generate_error_body(&err.to_string(), state.hard_reload, is_reload)
-                } else {
-                    Body::from(err.to_string())
-                },
-            );
+            if is_html(&mime) || accepts_html(&req) {
+                return error_page(&state, status_code, &err.to_string(), is_reload, injection)
+                    .await;
+            }
+            return (status_code, headers, Body::from(err.to_string()));
         }
     };
 
+    if state.mime_types.should_sniff(&path) {
+        let mut head = Vec::new();
+        let sniffed = match (&mut file).take(SNIFF_LENGTH).read_to_end(&mut head).await {
+            Ok(_) => file.rewind().await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = sniffed {
+            log::error!("Failed to read {path:?}: {err}");
+            return error_page(
+                &state,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &err.to_string(),
+                is_reload,
+                injection,
+            )
+            .await;
+        }
+        mime = sniff(&head);
+        headers.insert(header::CONTENT_TYPE, content_type_header(&mime));
+    }
+
     // Construct the response.
-    if mime == "text/html" {
-        let text = match String::from_utf8(file) {
-            Ok(text) => text,
-            Err(err) => {
-                log::error!("Failed to read {path:?} as utf-8: {err}");
-                let html = generate_error_body(&err.to_string(), state.hard_reload, is_reload);
-                return (StatusCode::INTERNAL_SERVER_ERROR, headers, html);
+    let is_markdown = state.markdown && is_markdown(&path);
+    if is_html(&mime) || is_markdown {
+        let mut bytes = Vec::with_capacity(metadata.len() as usize);
+        if let Err(err) = file.read_to_end(&mut bytes).await {
+            log::error!("Failed to read {path:?}: {err}");
+            return error_page(
+                &state,
+                StatusCode::INTERNAL_SERVER_ERROR,
+                &err.to_string(),
+                is_reload,
+                injection,
+            )
+            .await;
+        }
+        let relative_path = path.strip_prefix(root).unwrap_or(Path::new(""));
+        if state.includes && !is_markdown {
+            bytes = resolve_includes(bytes, layers, relative_path, &state.included_files).await;
+        }
+        if state.templates && !is_markdown {
+            // Rendered pages are UTF-8, so pages in other encodings cannot be templates.
+            let source = utf16_to_utf8(&bytes).unwrap_or(bytes);
+            let is_utf8 = detect_charset(&source).is_none_or(|charset| is_utf8_label(&charset));
+            let rendered = match String::from_utf8(source) {
+                Ok(source) if is_utf8 => {
+                    let name = template_name(relative_path);
+                    let layers = layers.to_vec();
+                    let roots = state.roots.clone();
+                    let included_files = state.included_files.clone();
+                    tokio::task::spawn_blocking(move || {
+                        render_template(&source, &name, &layers, &roots, &included_files)
+                    })
+                    .await
+                    .unwrap_or_else(|err| Err(err.to_string()))
+                }
+                _ => Err(format!(
+                    "Templates must be encoded in UTF-8, but {} is not",
+                    relative_path.display()
+                )),
+            };
+            match rendered {
+                Ok(html) => bytes = html.into_bytes(),
+                Err(err_msg) => {
+                    log::error!("Failed to render {path:?}: {err_msg}");
+                    // Custom error pages would hide where the error is.
+                    let script = match injection {
+                        Injection::Never => String::new(),
+                        _ => format_script(state.hard_reload, is_reload, true),
+                    };
+                    let body = format!("<pre>{}</pre>", escape_html(&err_msg));
+                    headers.insert(
+                        header::CONTENT_TYPE,
+                        HeaderValue::from_static("text/html; charset=utf-8"),
+                    );
+                    return (
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        headers,
+                        error_html(&script, &body),
+                    );
+                }
             }
-        };
+        }
+        if is_markdown {
+            let markdown = String::from_utf8_lossy(&bytes).into_owned();
+            let title = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            match tokio::task::spawn_blocking(move || render_markdown(&markdown, &title)).await {
+                Ok(html) => bytes = html.into_bytes(),
+                Err(err) => {
+                    log::error!("Failed to render {path:?}: {err}");
+                    return error_page(
+                        &state,
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        &err.to_string(),
+                        is_reload,
+                        injection,
+                    )
+                    .await;
+                }
+            }
+        }
         let script = format_script(state.hard_reload, is_reload, false);
-        file = format!("{text}{script}").into_bytes();
-    } else if state.hard_reload {
+        let html_mime = if is_markdown {
+            "text/html"
+        } else {
+            mime.essence_str()
+        };
+        let (file, content_type) =
+            inject_html(bytes, html_mime, &script, state.script_position, injection);
+        headers.append(header::VARY, HeaderValue::from_static(INJECTION_VARY));
+        headers.insert(header::CONTENT_TYPE, content_type);
+
+        // The body depends on the injected script, so the tag is derived from the
+        // content and `Last-Modified` is omitted.
+        let etag = content_etag(&file);
+        headers.append(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+        headers.append(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+        if is_not_modified(req.headers(), &etag, None) {
+            return (StatusCode::NOT_MODIFIED, headers, Body::empty());
+        }
+        return (StatusCode::OK, headers, Body::from(file));
+    }
+
+    let cache_control = if state.hard_reload {
         // allow client to cache assets for a smoother reload.
         // client handles preloading to refresh cache before reloading.
+        "max-age=30"
+    } else {
+        // let client revalidate with the validators below so an updated asset is
+        // never used stale.
+        "no-cache"
+    };
+    headers.append(
+        header::CACHE_CONTROL,
+        HeaderValue::from_static(cache_control),
+    );
+    headers.append(header::ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+
+    // Ranges always refer to the identity encoding, so precompressed files are only
+    // served for full requests.
+    if state.precompressed {
+        append_vary(&mut headers);
+        if !req.headers().contains_key(header::RANGE)
+            && let Some((encoding, sibling, sibling_file, sibling_metadata)) =
+                open_precompressed(&path, &accepted_encodings(req.headers())).await
+        {
+            headers.insert(
+                header::CONTENT_ENCODING,
+                HeaderValue::from_static(encoding.as_str()),
+            );
+            (file, metadata, path) = (sibling_file, sibling_metadata, sibling);
+        }
+    }
+
+    let len = metadata.len();
+    let modified = metadata.modified().ok();
+    let etag = file_etag(len, modified);
+    headers.append(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    if let Some(modified) = modified {
         headers.append(
-            header::CACHE_CONTROL,
-            HeaderValue::from_str("max-age=30").unwrap(),
+            header::LAST_MODIFIED,
+            HeaderValue::from_str(&http_date(modified)).unwrap(),
         );
     }
+    if is_not_modified(req.headers(), &etag, modified) {
+        return (StatusCode::NOT_MODIFIED, headers, Body::empty());
+    }
 
-    (StatusCode::OK, headers, Body::from(file))
+    match requested_ranges(req.headers(), len, &etag, modified) {
+        ByteRanges::Full => {
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(len));
+            let body = Body::from_stream(ReaderStream::new(file));
+            (StatusCode::OK, headers, body)
+        }
+        ByteRanges::Unsatisfiable => {
+            headers.insert(
+                header::CONTENT_RANGE,
+                HeaderValue::from_str(&format!("bytes */{len}")).unwrap(),
+            );
+            (StatusCode::RANGE_NOT_SATISFIABLE, headers, Body::empty())
+        }
+        ByteRanges::Partial(ranges) => {
+            if let [range] = ranges.as_slice() {
+                headers.insert(
+                    header::CONTENT_RANGE,
+                    HeaderValue::from_str(&content_range(range, len)).unwrap(),
+                );
+                headers.insert(
+                    header::CONTENT_LENGTH,
+                    HeaderValue::from(range.end() - range.start() + 1),
+                );
+                return match file_part(file, range).await {
+                    Ok(part) => (
+                        StatusCode::PARTIAL_CONTENT,
+                        headers,
+                        Body::from_stream(part),
+                    ),
+                    Err(err) => {
+                        log::error!("Failed to read {path:?}: {err}");
+                        (
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            HeaderMap::new(),
+                            Body::from(err.to_string()),
+                        )
+                    }
+                };
+            }
+            let boundary = multipart_boundary();
+            let content_type = content_type_header(&mime);
+            let content_type = content_type.to_str().unwrap_or_default();
+            let (content_length, body) = multipart_body(file, len, ranges, content_type, &boundary);
+            headers.insert(
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(&format!("multipart/byteranges; boundary={boundary}"))
+                    .unwrap(),
+            );
+            headers.insert(header::CONTENT_LENGTH, HeaderValue::from(content_length));
+            (StatusCode::PARTIAL_CONTENT, headers, body)
+        }
+    }
+}
+
+fn redirect(location: &str) -> (StatusCode, HeaderMap, Body) {
+    let mut headers = HeaderMap::new();
+    headers.append(header::LOCATION, HeaderValue::from_str(location).unwrap());
+    (StatusCode::TEMPORARY_REDIRECT, headers, Body::empty())
 }
 
-/// Inject the address into the websocket script and wrap it in a script tag
+enum CleanUrl {
+    /// Redirect to the canonical clean URL
+    Redirect(String),
+    /// Serve this `.html` file
+    File(PathBuf),
+    /// Resolve the path as usual
+    Unchanged,
+}
+
+/// Resolve extensionless URL paths to `.html` files.
+///
+/// For `/about`, an existing file `about` takes precedence over `about.html`, which takes
+/// precedence over a directory `about/`. A trailing slash always refers to a directory,
+/// so `/about/` is redirected to `/about` if only `about.html` exists.
+///
+/// If enabled, `/about.html` is redirected to `/about`, and `/about/index.html` to
+/// `/about/`.
+async fn resolve_clean_url(
+    state: &AppState,
+    uri_path: &str,
+    layers: &[PathBuf],
+    relative_path: &Path,
+    is_file: bool,
+    is_dir: bool,
+) -> CleanUrl {
+    let existing_file = |relative_path: PathBuf| async move {
+        let path = resolve_in_layers(layers, &relative_path).await;
+        fs::metadata(&path)
+            .await
+            .is_ok_and(|metadata| metadata.is_file())
+            .then_some(path)
+    };
+
+    if uri_path.ends_with('/') {
+        if !is_dir
+            && uri_path != "/"
+            && existing_file(with_html_extension(relative_path))
+                .await
+                .is_some()
+        {
+            return CleanUrl::Redirect(uri_path.trim_end_matches('/').to_string());
+        }
+        return CleanUrl::Unchanged;
+    }
+
+    if !is_file {
+        return match existing_file(with_html_extension(relative_path)).await {
+            Some(path) => CleanUrl::File(path),
+            None => CleanUrl::Unchanged,
+        };
+    }
+
+    if state.redirect_html_extension
+        && let Some(clean_path) = uri_path.strip_suffix(".html")
+    {
+        if let Some(dir_path) = clean_path.strip_suffix("/index") {
+            return CleanUrl::Redirect(format!("{dir_path}/"));
+        }
+        // An existing file without the extension would shadow this one.
+        let shadowing_path = relative_path.with_extension("");
+        if existing_file(shadowing_path).await.is_none() {
+            return CleanUrl::Redirect(clean_path.to_string());
+        }
+    }
+    CleanUrl::Unchanged
+}
+
+fn with_html_extension(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(".html");
+    PathBuf::from(path)
+}
+
+/// The `Content-Type` of `mime`, where text is UTF-8 unless the type says otherwise.
+fn content_type_header(mime: &Mime) -> HeaderValue {
+    let content_type = if mime.type_() == mime::TEXT && mime.get_param(mime::CHARSET).is_none() {
+        format!("{}; charset=utf-8", mime.as_ref())
+    } else {
+        mime.as_ref().to_string()
+    };
+    // Types are validated when they are parsed.
+    HeaderValue::from_str(&content_type).unwrap()
+}
+
+/// Check if a request could be a client-side route of a single-page application, that is,
+/// a navigation accepting HTML to a path without a file extension.
+fn is_history_route(req: &Request<Body>, path: &Path) -> bool {
+    accepts_html(req) && path.extension().is_none()
+}
+
+/// List a directory for tools rather than browsers, even if it has an `index.html`.
+async fn json_listing(
+    state: &AppState,
+    uri_path: &str,
+    layers: &[PathBuf],
+    relative_path: PathBuf,
+    sort: ListingSort,
+    json: JsonListing,
+) -> (StatusCode, HeaderMap, Body) {
+    let mut headers = HeaderMap::new();
+    headers.append(header::VARY, HeaderValue::from_static("Accept"));
+    if state.auto_ignore {
+        let path = resolve_in_layers(layers, &relative_path).await;
+        let root = find_root(layers, &path).unwrap_or(&layers[0]);
+        if !is_ignored(root, &path).is_ok_and(|ignored| !ignored) {
+            let err_msg = "Unable to access ignored or hidden file, because `--ignore` is enabled";
+            return (StatusCode::FORBIDDEN, headers, Body::from(err_msg));
+        }
+    }
+    let layers = layers.to_vec();
+    let auto_ignore = state.auto_ignore;
+    let listing_path = uri_path.to_string();
+    let listing = tokio::task::spawn_blocking(move || {
+        render_json_listing(
+            &listing_path,
+            &layers,
+            &relative_path,
+            auto_ignore,
+            sort,
+            json,
+        )
+    })
+    .await
+    .unwrap_or_else(|err| Err(std::io::Error::other(err)));
+    match listing {
+        Ok(listing) => {
+            headers.append(
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json"),
+            );
+            (StatusCode::OK, headers, Body::from(listing))
+        }
+        Err(err) => {
+            let err_msg = format!("Failed to read directory: {err}");
+            log::error!("{err_msg}");
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                headers,
+                Body::from(err_msg),
+            )
+        }
+    }
+}
+
+fn accepts_html(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"))
+}
+
+/// Create the script tag to inject into HTML
 fn format_script(hard_reload: bool, is_reload: bool, is_error: bool) -> String {
     match (is_reload, is_error) {
         // successful reload, inject the reload payload
-        (true, false) => format!("<script>{RELOAD_PAYLOAD}</script>"),
+        (true, false) => format!(r#"<script src="{RELOAD_SCRIPT_PATH}"></script>"#),
         // failed reload, don't inject anything so the client polls again
         (true, true) => String::new(),
         // normal connection, inject the websocket client
         _ => {
             let hard = if hard_reload { "true" } else { "false" };
-            format!(r#"<script>{WEBSOCKET_FUNCTION}({hard})</script>"#)
+            format!(r#"<script src="{CLIENT_SCRIPT_PATH}" data-hard="{hard}"></script>"#)
         }
     }
 }
 
-fn generate_error_body(err_msg: &str, hard_reload: bool, is_reload: bool) -> Body {
-    let script = format_script(hard_reload, is_reload, true);
-    error_html(&script, err_msg)
+/// Respond with an error page.
+///
+/// If the roots contain a page named after the status code, like `404.html`, it is used
+/// instead of the built-in one, just like GitHub Pages and Netlify do.
+async fn error_page(
+    state: &AppState,
+    status: StatusCode,
+    err_msg: &str,
+    is_reload: bool,
+    injection: Injection,
+) -> (StatusCode, HeaderMap, Body) {
+    let mut headers = HeaderMap::new();
+    // The script lets the client tell a failed reload from a successful one.
+    // Error pages are often fragments, which still get the script unless the request is
+    // known not to want it.
+    let injection = match injection {
+        Injection::Never => Injection::Never,
+        _ => Injection::Always,
+    };
+    let script = match injection {
+        Injection::Never => String::new(),
+        _ => format_script(state.hard_reload, is_reload, true),
+    };
+    let custom_page = format!("{}.html", status.as_u16());
+    let custom_page = resolve_in_layers(&state.roots, Path::new(&custom_page)).await;
+    let (content_type, body) = match fs::read(&custom_page).await {
+        Ok(page) => {
+            let (page, content_type) =
+                inject_html(page, "text/html", &script, state.script_position, injection);
+            (content_type, Body::from(page))
+        }
+        Err(_) => (
+            HeaderValue::from_static("text/html; charset=utf-8"),
+            error_html(&script, err_msg),
+        ),
+    };
+    headers.append(header::CONTENT_TYPE, content_type);
+    headers.append(header::VARY, HeaderValue::from_static(INJECTION_VARY));
+    (status, headers, body)
+}
+
+/// Decide whether an HTML response to a request for `uri_path` gets the client script.
+fn injection_for(state: &AppState, headers: &HeaderMap, uri_path: &str) -> Injection {
+    let decoded_path = percent_decode_str(uri_path).decode_utf8_lossy();
+    if state.no_inject.is_match(decoded_path.as_ref()) {
+        Injection::Never
+    } else {
+        Injection::for_request(headers)
+    }
 }
@@ -1,9 +1,9 @@
 use axum::body::Body;
 
-pub(crate) fn index_html(index: &str, script: &str, body: &str) -> Body {
+pub(crate) fn index_html(index: &str, script: &str, breadcrumbs: &str, table: &str) -> Body {
     Body::from(format!(
         include_str!("../templates/index.html"),
-        index, script, body
+        index, script, breadcrumbs, table
     ))
 }
 
@@ -0,0 +1,145 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use minijinja::{Environment, Error, ErrorKind, Value, context};
+
+use super::include::IncludedFiles;
+
+/// Directory of the data files of templates in the roots, like `data/site.json`.
+const DATA_DIR: &str = "data";
+
+/// Render an HTML page as a template, whose path in the `layers` is `name`.
+///
+/// Templates get the data files of the `roots` as `data`, like `data.site.title` for
+/// `data/site.json`, and the environment variables as `env`. Other templates can be
+/// included or extended by their paths in the `layers`. Data files and other templates
+/// are added to `included` so that changing them reloads the page.
+///
+/// Errors are described with the file and line they occur in. This does blocking IO, so
+/// it should be run with [tokio::task::spawn_blocking].
+pub(crate) fn render_template(
+    source: &str,
+    name: &str,
+    layers: &[PathBuf],
+    roots: &[PathBuf],
+    included: &IncludedFiles,
+) -> Result<String, String> {
+    let data = load_data(roots, included)?;
+    let mut env = Environment::new();
+    let loader_layers = layers.to_vec();
+    let loader_included = included.clone();
+    env.set_loader(move |name| load_template(&loader_layers, &loader_included, name));
+    let template = env
+        .template_from_named_str(name, source)
+        .map_err(describe_error)?;
+    // Variables that are not valid Unicode cannot be used in templates anyway.
+    let env_vars = std::env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .collect::<BTreeMap<_, _>>();
+    template
+        .render(context! { data => data, env => env_vars })
+        .map_err(describe_error)
+}
+
+/// Read `data/*.json`, `data/*.toml` and `data/*.yaml` of all roots, where the ones of
+/// earlier roots take precedence.
+fn load_data(roots: &[PathBuf], included: &IncludedFiles) -> Result<Value, String> {
+    let mut data = BTreeMap::new();
+    for root in roots.iter().rev() {
+        let Ok(entries) = fs::read_dir(root.join(DATA_DIR)) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let (Some(stem), Some(extension)) = (
+                path.file_stem().and_then(|stem| stem.to_str()),
+                path.extension().and_then(|extension| extension.to_str()),
+            ) else {
+                continue;
+            };
+            let parse = match extension.to_ascii_lowercase().as_str() {
+                "json" => |s: &str| serde_json::from_str(s).map_err(|err| err.to_string()),
+                "toml" => |s: &str| toml::from_str(s).map_err(|err| err.to_string()),
+                "yaml" | "yml" => {
+                    |s: &str| serde_yaml_ng::from_str(s).map_err(|err| err.to_string())
+                }
+                _ => continue,
+            };
+            included.write().unwrap().insert(path.clone());
+            let content = fs::read_to_string(&path)
+                .map_err(|err| format!("Failed to read {}: {err}", path.display()))?;
+            let value: serde_json::Value = parse(&content)
+                .map_err(|err| format!("Failed to parse {}: {err}", path.display()))?;
+            data.insert(stem.to_string(), value);
+        }
+    }
+    Ok(Value::from_serialize(&data))
+}
+
+/// Load the template at the path `name` of the first layer containing it.
+fn load_template(
+    layers: &[PathBuf],
+    included: &IncludedFiles,
+    name: &str,
+) -> Result<Option<String>, Error> {
+    let mut relative_path = PathBuf::new();
+    for segment in name.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                return Err(Error::new(
+                    ErrorKind::InvalidOperation,
+                    format!("Template `{name}` is outside of the root"),
+                ));
+            }
+            segment => relative_path.push(segment),
+        }
+    }
+    let Some(path) = layers
+        .iter()
+        .map(|layer| layer.join(&relative_path))
+        .find(|path| path.is_file())
+    else {
+        return Ok(None);
+    };
+    included.write().unwrap().insert(path.clone());
+    fs::read_to_string(&path).map(Some).map_err(|err| {
+        Error::new(
+            ErrorKind::InvalidOperation,
+            format!("Failed to read {}", path.display()),
+        )
+        .with_source(err)
+    })
+}
+
+/// Describe an error with its template, line and the source around it.
+fn describe_error(err: Error) -> String {
+    let mut description = err.to_string();
+    if let Some(source) = std::error::Error::source(&err) {
+        description.push_str(&format!(": {source}"));
+    }
+    let debug_info = err.display_debug_info().to_string();
+    // The referenced variables are left out, since they may include all environment
+    // variables and the page can be seen by anyone on the network.
+    let source = match debug_info.find("Referenced variables:") {
+        Some(end) => &debug_info[..end],
+        None => &debug_info,
+    };
+    if !source.trim().is_empty() {
+        description.push_str("\n\n");
+        description.push_str(source.trim_end());
+    }
+    description
+}
+
+/// The name of a template at `relative_path`, like `blog/post.html`.
+pub(crate) fn template_name(relative_path: &Path) -> String {
+    relative_path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
@@ -0,0 +1,135 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    path::PathBuf,
+    sync::Arc,
+    time::Duration,
+};
+
+use local_ip_address::local_ip;
+use rustls::{
+    ServerConfig,
+    crypto::ring,
+    pki_types::{CertificateDer, PrivateKeyDer, pem::PemObject},
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc::{self, Receiver},
+};
+use tokio_rustls::{TlsAcceptor, server::TlsStream};
+
+/// Clients that do not finish the TLS handshake within this time are dropped.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Certificate used to serve HTTPS.
+pub enum Certificate {
+    /// Generate a self-signed certificate for `localhost`, the loopback addresses and
+    /// the local IP address
+    SelfSigned,
+    /// Load the certificate chain and the private key from PEM files
+    Pem { cert: PathBuf, key: PathBuf },
+}
+
+/// Build the TLS acceptor of a listener bound to `addr`.
+pub(crate) fn create_tls_acceptor(
+    certificate: Certificate,
+    addr: SocketAddr,
+) -> Result<TlsAcceptor, String> {
+    let (certs, key) = match certificate {
+        Certificate::SelfSigned => self_signed_certificate(addr)?,
+        Certificate::Pem { cert, key } => {
+            let certs = CertificateDer::pem_file_iter(&cert)
+                .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+                .map_err(|err| format!("Failed to read certificate {cert:?}: {err}"))?;
+            let key = PrivateKeyDer::from_pem_file(&key)
+                .map_err(|err| format!("Failed to read private key {key:?}: {err}"))?;
+            (certs, key)
+        }
+    };
+
+    let config = ServerConfig::builder_with_provider(Arc::new(ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|err| format!("Failed to create TLS configuration: {err}"))?;
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+fn self_signed_certificate(
+    addr: SocketAddr,
+) -> Result<(Vec<CertificateDer<'static>>, PrivateKeyDer<'static>), String> {
+    let mut subject_alt_names = vec![
+        "localhost".to_string(),
+        IpAddr::V4(Ipv4Addr::LOCALHOST).to_string(),
+        IpAddr::V6(Ipv6Addr::LOCALHOST).to_string(),
+    ];
+    match local_ip() {
+        Ok(ip) => subject_alt_names.push(ip.to_string()),
+        Err(err) => log::warn!("Failed to get local IP address: {err}"),
+    }
+    if !addr.ip().is_unspecified() {
+        subject_alt_names.push(addr.ip().to_string());
+    }
+    subject_alt_names.sort();
+    subject_alt_names.dedup();
+
+    let certified = rcgen::generate_simple_self_signed(subject_alt_names)
+        .map_err(|err| format!("Failed to generate self-signed certificate: {err}"))?;
+    let key = PrivateKeyDer::Pkcs8(certified.key_pair.serialize_der().into());
+    Ok((vec![certified.cert.der().clone()], key))
+}
+
+/// A listener accepting TLS connections, for [axum::serve].
+///
+/// Handshakes are done in separate tasks so that a slow client cannot block others.
+pub(crate) struct TlsListener {
+    rx: Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: io::Result<SocketAddr>,
+}
+
+impl TlsListener {
+    pub(crate) fn new(tcp_listener: TcpListener, acceptor: TlsAcceptor) -> Self {
+        let local_addr = tcp_listener.local_addr();
+        let (tx, rx) = mpsc::channel(16);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = match tcp_listener.accept().await {
+                    Ok(accepted) => accepted,
+                    Err(err) => {
+                        log::error!("Failed to accept connection: {err}");
+                        tokio::time::sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                };
+                let acceptor = acceptor.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = tx.send((stream, addr)).await;
+                        }
+                        Ok(Err(err)) => log::debug!("TLS handshake with {addr} failed: {err}"),
+                        Err(_) => log::debug!("TLS handshake with {addr} timed out"),
+                    }
+                });
+            }
+        });
+        Self { rx, local_addr }
+    }
+}
+
+impl axum::serve::Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        // The sender lives as long as the accepting task, which never stops.
+        self.rx.recv().await.unwrap()
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        match &self.local_addr {
+            Ok(addr) => Ok(*addr),
+            Err(err) => Err(io::Error::new(err.kind(), err.to_string())),
+        }
+    }
+}
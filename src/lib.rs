@@ -18,12 +18,22 @@ mod file_layer;
 mod http_layer;
 mod utils;
 
-pub use http_layer::server::Options;
+pub use file_layer::mount::Mount;
+pub use http_layer::{
+    access_log::AccessLogFormat, auth::BasicAuth, content_type::MimeOverride, headers::HeaderRule,
+    inject::ScriptPosition, proxy::Proxy, server::Options, tls::Certificate,
+};
 
 use file_layer::watcher::{create_poll_watcher, watch};
+use globset::{Glob, GlobSetBuilder};
 use http_layer::{
-    listener::create_listener,
+    access_log::AccessLog,
+    content_type::MimeTypes,
+    include::IncludedFiles,
+    listener::{create_listener, print_listening_on_link},
+    proxy::check_proxy_prefixes,
     server::{AppState, create_server, serve},
+    tls::create_tls_acceptor,
 };
 use local_ip_address::local_ip;
 use notify::{PollWatcher, RecommendedWatcher, Watcher};
@@ -39,11 +49,13 @@ use tokio::{
     net::TcpListener,
     sync::{broadcast, mpsc::Receiver},
 };
+use tokio_rustls::TlsAcceptor;
 
 use crate::file_layer::watcher::create_recommended_watcher;
 
 pub struct Listener<W: Watcher> {
     tcp_listener: TcpListener,
+    tls: Option<TlsAcceptor>,
     root_path: PathBuf,
     debouncer: Debouncer<W, RecommendedCache>,
     rx: Receiver<Result<Vec<DebouncedEvent>, Vec<notify::Error>>>,
@@ -62,29 +74,125 @@ impl<W: Watcher + Send + 'static> Listener<W> {
     pub async fn start(self, options: Options) -> Result<(), Box<dyn Error>> {
         let (tx, _) = broadcast::channel(16);
 
+        let mut mounts = Vec::with_capacity(options.mounts.len());
+        for mount in options.mounts {
+            let path = get_absolute_path(&mount.path)?;
+            if !path.is_dir() {
+                return Err(format!("Mounted path {path:?} is not a directory").into());
+            }
+            log::info!("Mounting {} on {}", path.display(), mount.prefix);
+            mounts.push(Mount {
+                prefix: mount.prefix,
+                path,
+            });
+        }
+        let mut roots = vec![self.root_path];
+        for overlay in options.overlays {
+            let path = get_absolute_path(&overlay)?;
+            if !path.is_dir() {
+                return Err(format!("Overlay root {path:?} is not a directory").into());
+            }
+            log::info!("Falling through to {}", path.display());
+            roots.push(path);
+        }
+        check_proxy_prefixes(&options.proxies)?;
+        let mut no_inject = GlobSetBuilder::new();
+        for glob in &options.no_inject {
+            let glob = Glob::new(glob).map_err(|err| format!("Invalid glob `{glob}`: {err}"))?;
+            no_inject.add(glob);
+        }
+        let no_inject = no_inject.build()?;
+        let access_log = match options.access_log {
+            Some(format) => Some(Arc::new(AccessLog::new(
+                format,
+                options.access_log_file.as_deref(),
+            )?)),
+            None => None,
+        };
+        let mut watched_paths = roots.clone();
+        watched_paths.extend(mounts.iter().map(|mount| mount.path.clone()));
+
+        if options.auth.is_some() && self.tls.is_none() {
+            log::warn!("Credentials are sent in plain text, consider enabling HTTPS");
+        }
+
         let arc_tx = Arc::new(tx);
+        let included_files = IncludedFiles::default();
         let app_state = AppState {
             hard_reload: options.hard_reload,
             index_listing: options.index_listing,
             auto_ignore: options.auto_ignore,
+            compression: options.compression,
+            precompressed: options.precompressed,
+            spa: options.spa,
+            mounts,
+            proxies: options.proxies,
+            https: self.tls.is_some(),
+            headers: options.headers,
+            clean_urls: options.clean_urls,
+            redirect_html_extension: options.redirect_html_extension,
+            auth: options.auth,
+            allowed_hosts: options.allowed_hosts,
+            script_position: options.script_position,
+            no_inject,
+            markdown: options.markdown,
+            includes: options.includes,
+            templates: options.templates,
+            mime_types: MimeTypes::new(
+                &options.mime_types,
+                options.default_mime,
+                options.sniff_mime,
+            ),
+            included_files: included_files.clone(),
+            access_log,
             tx: arc_tx.clone(),
-            root: self.root_path.clone(),
+            roots,
         };
 
         let watcher_future = tokio::spawn(watch(
-            self.root_path,
+            watched_paths,
             self.debouncer,
             self.rx,
             arc_tx,
             options.auto_ignore,
+            included_files,
         ));
-        let server_future = tokio::spawn(serve(self.tcp_listener, create_server(app_state)));
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+        print_listening_on_link(&self.tcp_listener, scheme);
+        let server_future =
+            tokio::spawn(serve(self.tcp_listener, self.tls, create_server(app_state)));
 
         tokio::try_join!(watcher_future, server_future)?;
 
         Ok(())
     }
 
+    /// Serve HTTPS instead of HTTP, using the given certificate.
+    ///
+    /// This is required for features like service workers or `getUserMedia` to work on
+    /// other devices in the local network, because browsers only allow them in secure
+    /// contexts.
+    ///
+    /// ```
+    /// use live_server::{listen, Certificate, Options};
+    ///
+    /// async fn serve() -> Result<(), Box<dyn std::error::Error>> {
+    ///     listen("127.0.0.1:8080", "./")
+    ///         .await?
+    ///         .https(Certificate::SelfSigned)?
+    ///         .start(Options::default())
+    ///         .await
+    /// }
+    /// ```
+    pub fn https(mut self, certificate: Certificate) -> Result<Self, String> {
+        let addr = self
+            .tcp_listener
+            .local_addr()
+            .map_err(|err| err.to_string())?;
+        self.tls = Some(create_tls_acceptor(certificate, addr)?);
+        Ok(self)
+    }
+
     /// Return the link of the server, like `http://127.0.0.1:8080`.
     ///
     /// ```
@@ -108,9 +216,11 @@ impl<W: Watcher + Send + 'static> Listener<W> {
             false => host,
         };
 
+        let scheme = if self.tls.is_some() { "https" } else { "http" };
+
         Ok(match host {
-            IpAddr::V4(host) => format!("http://{host}:{port}"),
-            IpAddr::V6(host) => format!("http://[{host}]:{port}"),
+            IpAddr::V4(host) => format!("{scheme}://{host}:{port}"),
+            IpAddr::V6(host) => format!("{scheme}://[{host}]:{port}"),
         })
     }
 }
@@ -136,6 +246,7 @@ pub async fn listen(
 
     Ok(Listener {
         tcp_listener,
+        tls: None,
         debouncer,
         root_path: abs_root,
         rx,
@@ -167,6 +278,7 @@ pub async fn listen_poll(
 
     Ok(Listener {
         tcp_listener,
+        tls: None,
         debouncer,
         root_path: abs_root,
         rx,
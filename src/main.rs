@@ -1,16 +1,28 @@
 use clap::Parser;
 use env_logger::Env;
-use live_server::{Listener, Options, listen, listen_poll};
+use std::path::PathBuf;
+
+use live_server::{
+    AccessLogFormat, BasicAuth, Certificate, HeaderRule, Listener, MimeOverride, Mount, Options,
+    Proxy, ScriptPosition, listen, listen_poll,
+};
+use mime_guess::Mime;
 use notify::Watcher;
 
 /// Launch a local network server with live reload feature for static pages.
 #[derive(Parser)]
 #[clap(version)]
 struct Args {
-    /// Set the root path of the static assets
-    #[clap(default_value = ".")]
-    root: String,
+    /// Set the root paths of the static assets
+    ///
+    /// If multiple roots are given, like `dist public`, each file is served from the first
+    /// root containing it, and directory listings merge the entries of all roots.
+    #[clap(default_value = ".", value_name = "ROOT")]
+    roots: Vec<String>,
     /// Show directory listings if there is no index.html
+    ///
+    /// Directories are also listed as JSON for requests with `Accept: application/json` or
+    /// `?format=json`, including subdirectories with `&recursive` or `&depth=N`.
     #[clap(long)]
     index: bool,
     /// Set the listener host
@@ -45,10 +57,185 @@ struct Args {
     /// atomically replaced, or when the monitored directory itself is moved or renamed.
     #[clap(long)]
     poll: bool,
+    /// Compress responses with gzip, brotli or zstd on the fly
+    #[clap(long)]
+    compress: bool,
+    /// Serve precompressed files like `foo.js.br` or `foo.js.gz` if they exist
+    #[clap(long)]
+    precompressed: bool,
+    /// Serve a fallback page for unknown routes of single-page applications
+    ///
+    /// Any HTML request to a path without a file extension that does not exist will be
+    /// answered with this file (`index.html` by default), so that client-side routers work.
+    #[clap(long, value_name = "FILE")]
+    spa: Option<Option<String>>,
+    /// Forward requests below a path prefix to an upstream server, like `/api=http://127.0.0.1:3000`
+    ///
+    /// The request path is appended to the upstream URL unchanged. This option can be used
+    /// multiple times.
+    #[clap(long, value_name = "PREFIX=UPSTREAM")]
+    proxy: Vec<Proxy>,
+    /// Serve HTTPS with a self-signed certificate, unless `--cert` and `--key` are given
+    ///
+    /// Browsers only allow features like service workers, `getUserMedia` or WebAuthn in
+    /// secure contexts, which a plain HTTP server in the local network is not.
+    #[clap(long)]
+    https: bool,
+    /// Certificate chain in PEM format to serve HTTPS with
+    #[clap(long, value_name = "PATH", requires = "key")]
+    cert: Option<PathBuf>,
+    /// Private key in PEM format to serve HTTPS with
+    #[clap(long, value_name = "PATH", requires = "cert")]
+    key: Option<PathBuf>,
+    /// Add a header to responses, like `Name: value` or `GLOB=Name: value`
+    ///
+    /// The glob is matched against the URL path, so `*.wasm=Cache-Control: no-store` applies
+    /// to all WebAssembly files and `/fonts/**=Access-Control-Allow-Origin: *` to everything
+    /// below `/fonts`. This option can be used multiple times, and later headers replace
+    /// earlier ones with the same name.
+    #[clap(long = "header", value_name = "HEADER")]
+    headers: Vec<HeaderRule>,
+    /// Serve `about.html` for `/about`
+    ///
+    /// An existing file `about` takes precedence over `about.html`, which takes precedence
+    /// over a directory `about/`.
+    #[clap(long)]
+    clean_urls: bool,
+    /// Redirect `/about.html` to `/about` and `/about/index.html` to `/about/`
+    #[clap(long, requires = "clean_urls")]
+    clean_urls_redirect: bool,
+    /// Serve another directory under a path prefix, like `/assets=../shared/static`
+    ///
+    /// Changes in mounted directories trigger reload as well. This option can be used
+    /// multiple times.
+    #[clap(long, value_name = "PREFIX=PATH")]
+    mount: Vec<Mount>,
+    /// Require HTTP Basic authentication with `USER:PASSWORD`
+    ///
+    /// The credentials can also be given in the `LIVE_SERVER_AUTH` environment variable,
+    /// which keeps them out of the shell history and the process list.
+    #[clap(
+        long,
+        value_name = "USER:PASSWORD",
+        env = "LIVE_SERVER_AUTH",
+        hide_env_values = true,
+        conflicts_with = "auth_file"
+    )]
+    auth: Option<BasicAuth>,
+    /// Require HTTP Basic authentication with the users of an htpasswd file
+    ///
+    /// Passwords may be hashed with bcrypt (`htpasswd -B`) or SHA-1 (`htpasswd -s`), or
+    /// stored in plain text.
+    #[clap(long, value_name = "PATH")]
+    auth_file: Option<PathBuf>,
+    /// Accept requests to this host name, like `example.test`
+    ///
+    /// To prevent DNS rebinding attacks, only IP addresses, `localhost` and its subdomains
+    /// are accepted by default. A leading `.` like `.example.test` accepts the subdomains
+    /// too, and `*` accepts all host names. This option can be used multiple times.
+    #[clap(long = "allowed-host", value_name = "HOST")]
+    allowed_hosts: Vec<String>,
+    /// Inject the reload script before `</body>` (`body`) or `</head>` (`head`)
+    ///
+    /// If the document has neither of them, the script is appended to the end.
+    #[clap(long, value_name = "POSITION", default_value = "body")]
+    script_position: ScriptPosition,
+    /// Never inject the reload script into HTML files matching a glob, like `/emails/**`
+    ///
+    /// The glob is matched against the URL path. The script is also not injected into HTML
+    /// fragments requested by HTMX, Turbo Frames or `fetch()`. This option can be used
+    /// multiple times.
+    #[clap(long, value_name = "GLOB")]
+    no_inject: Vec<String>,
+    /// Render Markdown files as HTML pages with highlighted code blocks
+    ///
+    /// Directories without an index.html show their README.md instead.
+    #[clap(long)]
+    markdown: bool,
+    /// Replace `<!--#include file="partials/nav.html" -->` in HTML files with the file
+    ///
+    /// `file` is relative to the including file, and `virtual` is relative to the root if
+    /// it starts with `/`. Includes cannot leave the root, and pages reload when the files
+    /// they include change, even if those are ignored.
+    #[clap(long)]
+    includes: bool,
+    /// Render HTML files as templates with variables, loops and conditionals
+    ///
+    /// Templates get the data files in `data/`, like `{{ data.site.title }}` for
+    /// `data/site.json`, `.toml` or `.yaml`, and the environment variables, like
+    /// `{{ env.HOME }}`. Other templates are included or extended by their paths in the root.
+    /// Templates must be encoded in UTF-8.
+    #[clap(long)]
+    templates: bool,
+    /// Serve files with an extension as a MIME type, like `ts=text/javascript`
+    ///
+    /// Files mapped to `text/html` or `application/xhtml+xml`, like `htm=text/html`, get the
+    /// reload script. This option can be used multiple times and takes precedence over
+    /// `--mime-file`.
+    #[clap(long, value_name = "EXT=TYPE")]
+    mime: Vec<MimeOverride>,
+    /// Read MIME types from a file of `EXT=TYPE` lines or a `mime.types` file of nginx or
+    /// Apache
+    #[clap(long, value_name = "PATH")]
+    mime_file: Option<PathBuf>,
+    /// MIME type of files with unknown extensions
+    #[clap(long, value_name = "TYPE", default_value = "text/plain")]
+    default_mime: Mime,
+    /// Detect the MIME type of files without an extension from their content
+    #[clap(long)]
+    sniff_mime: bool,
+    /// Log every request in the `common` or `combined` log format, or as `json`, like
+    /// `--access-log=json`
+    ///
+    /// The log is written to stderr unless `--access-log-file` is given. Only the JSON
+    /// format includes the duration of the requests.
+    #[clap(
+        long,
+        value_name = "FORMAT",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "combined"
+    )]
+    access_log: Option<AccessLogFormat>,
+    /// Append the access log to a file instead of writing it to stderr
+    #[clap(long, value_name = "PATH", requires = "access_log")]
+    access_log_file: Option<PathBuf>,
+}
+
+impl Args {
+    fn certificate(&self) -> Option<Certificate> {
+        match (&self.cert, &self.key) {
+            (Some(cert), Some(key)) => Some(Certificate::Pem {
+                cert: cert.clone(),
+                key: key.clone(),
+            }),
+            _ if self.https => Some(Certificate::SelfSigned),
+            _ => None,
+        }
+    }
 }
 
 // Workaround for https://github.com/rust-lang/rust/issues/63065
-async fn run_listener<W: Watcher + Send + 'static>(listener: Listener<W>, args: &Args) {
+async fn run_listener<W: Watcher + Send + 'static>(
+    listener: Listener<W>,
+    args: &Args,
+) -> Result<(), String> {
+    let listener = match args.certificate() {
+        Some(certificate) => listener.https(certificate)?,
+        None => listener,
+    };
+
+    let auth = match &args.auth_file {
+        Some(path) => Some(BasicAuth::from_htpasswd(path)?),
+        None => args.auth.clone(),
+    };
+
+    let mut mime_types = match &args.mime_file {
+        Some(path) => MimeOverride::from_file(path)?,
+        None => Vec::new(),
+    };
+    mime_types.extend(args.mime.iter().cloned());
+
     if let Some(page) = &args.open {
         let origin = listener.link().unwrap();
         let path = page.clone().unwrap_or_default();
@@ -64,9 +251,34 @@ async fn run_listener<W: Watcher + Send + 'static>(listener: Listener<W>, args:
             hard_reload: args.hard,
             index_listing: args.index,
             auto_ignore: args.ignore,
+            compression: args.compress,
+            precompressed: args.precompressed,
+            spa: args
+                .spa
+                .clone()
+                .map(|file| file.unwrap_or_else(|| "index.html".to_string()).into()),
+            proxies: args.proxy.clone(),
+            headers: args.headers.clone(),
+            clean_urls: args.clean_urls,
+            redirect_html_extension: args.clean_urls_redirect,
+            mounts: args.mount.clone(),
+            auth,
+            allowed_hosts: args.allowed_hosts.clone(),
+            script_position: args.script_position,
+            no_inject: args.no_inject.clone(),
+            markdown: args.markdown,
+            includes: args.includes,
+            templates: args.templates,
+            mime_types,
+            default_mime: args.default_mime.clone(),
+            sniff_mime: args.sniff_mime,
+            access_log: args.access_log,
+            access_log_file: args.access_log_file.clone(),
+            overlays: args.roots.iter().skip(1).map(PathBuf::from).collect(),
         })
         .await
-        .unwrap()
+        .map_err(|err| err.to_string())?;
+    Ok(())
 }
 
 #[tokio::main]
@@ -76,16 +288,17 @@ async fn main() -> Result<(), String> {
 
     let args = Args::parse();
     let Args {
-        host, port, root, ..
+        host, port, roots, ..
     } = &args;
+    // Clap guarantees at least one root because of the default value.
+    let root = &roots[0];
 
     let addr = format!("{host}:{port}");
     if args.poll {
         let listener = listen_poll(addr, root).await?;
-        run_listener(listener, &args).await;
+        run_listener(listener, &args).await
     } else {
         let listener = listen(addr, root).await?;
-        run_listener(listener, &args).await;
-    };
-    Ok(())
+        run_listener(listener, &args).await
+    }
 }
@@ -1,6 +1,6 @@
-use live_server::{Options, listen};
+use live_server::{BasicAuth, Certificate, MimeOverride, Options, listen};
 use reqwest::StatusCode;
-use std::fs;
+use std::{fs, path::PathBuf, time::Duration};
 
 #[tokio::test]
 async fn request() {
@@ -20,14 +20,21 @@ async fn request() {
     assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
-    let target_text = format!(
-        r#"{}<script>{}(false)</script>"#,
-        include_str!("./page/index.html"),
-        include_str!("../src/templates/websocket.js"),
-    )
-    .replace("\r\n", "\n");
+    let script = r#"<script src="/live-server/client.js" data-hard="false"></script>"#;
+    let target_text = include_str!("./page/index.html")
+        .replace("</body>", &format!("{script}</body>"))
+        .replace("\r\n", "\n");
     assert_eq!(text, target_text);
-    assert!(text.contains("<script>"));
+
+    // Test requesting the client script
+    let response = reqwest::get(format!("http://{HOST}/live-server/client.js"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let content_type = response.headers().get("content-type").unwrap();
+    assert_eq!(content_type, "text/javascript; charset=utf-8");
+    let text = response.text().await.unwrap();
+    assert!(text.starts_with(include_str!("../src/templates/websocket.js")));
 
     // Test requesting index.js
     let response = reqwest::get(format!("http://{HOST}/index.js"))
@@ -75,12 +82,10 @@ async fn request() {
     assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
-    let target_text = format!(
-        r#"{}<script>{}</script>"#,
-        include_str!("./page/index.html"),
-        include_str!("../src/templates/reload.js"),
-    )
-    .replace("\r\n", "\n");
+    let script = r#"<script src="/live-server/reload.js"></script>"#;
+    let target_text = include_str!("./page/index.html")
+        .replace("</body>", &format!("{script}</body>"))
+        .replace("\r\n", "\n");
     assert_eq!(text, target_text);
 
     // Test requesting non-existent html file with reload query does not inject script
@@ -94,7 +99,7 @@ async fn request() {
     assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap();
-    assert!(!text.contains("<script>"));
+    assert!(!text.contains("<script"));
 }
 
 #[tokio::test]
@@ -108,6 +113,7 @@ async fn disable_index_listing() {
                 hard_reload: true,
                 index_listing: false,
                 auto_ignore: false,
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -122,7 +128,7 @@ async fn disable_index_listing() {
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
     assert!(text.starts_with("<!DOCTYPE html>"));
-    assert!(text.contains("<script>"));
+    assert!(text.contains("<script src="));
 }
 
 #[tokio::test]
@@ -136,6 +142,7 @@ async fn enable_index_listing() {
                 hard_reload: true,
                 index_listing: true,
                 auto_ignore: false,
+                ..Default::default()
             })
             .await
             .unwrap();
@@ -149,10 +156,64 @@ async fn enable_index_listing() {
     assert_eq!(content_type, "text/html; charset=utf-8");
 
     let text = response.text().await.unwrap().replace("\r\n", "\n");
-    assert!(text.ends_with(
-        "<body><ul><li><a href=\"not_index.html\">not_index.html</a></li></ul></body>\n</html>\n"
-    ));
-    assert!(text.contains("<script>"));
+    assert!(text.contains("<a href=\"not_index.html\">&#x1F4DD; not_index.html</a>"));
+    assert!(!text.contains("<a href=\"..\">"));
+    assert!(text.contains("<script src="));
+}
+
+#[tokio::test]
+async fn rich_index_listing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let nested_dir = temp_dir.path().join("dir with spaces");
+    fs::create_dir(&nested_dir).unwrap();
+    fs::create_dir(nested_dir.join("z_dir")).unwrap();
+    fs::write(nested_dir.join("a.txt"), "a").unwrap();
+    fs::write(nested_dir.join("b.bin"), vec![0; 2048]).unwrap();
+    fs::write(nested_dir.join("<c>.png"), "png").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                index_listing: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let get = |query: &str| {
+        let url = format!("{origin}/dir%20with%20spaces/{query}");
+        async move { reqwest::get(url).await.unwrap().text().await.unwrap() }
+    };
+    let order = |text: &str| {
+        let mut names = ["z_dir/", "a.txt", "b.bin", "&lt;c&gt;.png"]
+            .into_iter()
+            .map(|name| (text.find(&format!(" {name}</a>")).unwrap(), name))
+            .collect::<Vec<_>>();
+        names.sort();
+        names.into_iter().map(|(_, name)| name).collect::<Vec<_>>()
+    };
+
+    // Directories come first, then files by name
+    let text = get("").await;
+    assert_eq!(order(&text), ["z_dir/", "&lt;c&gt;.png", "a.txt", "b.bin"]);
+    assert!(text.contains("<a href=\"%3Cc%3E.png\">&#x1F5BC;&#xFE0F; &lt;c&gt;.png</a>"));
+    assert!(text.contains("<td data-bytes=\"2048\">2.0 KiB</td>"));
+    assert!(text.contains("<td data-bytes=\"1\">1 B</td>"));
+    assert!(text.contains(r#"<a href="..">"#));
+
+    // Breadcrumbs link to each parent directory
+    assert!(text.contains(concat!(
+        r#"<nav><a href="/">/</a>"#,
+        r#"<a href="/dir%20with%20spaces/">dir with spaces</a>/</nav>"#
+    )));
+
+    // Columns are sorted with query parameters, and the current one can be reversed
+    let text = get("?sort=size&order=desc").await;
+    assert_eq!(order(&text), ["z_dir/", "b.bin", "&lt;c&gt;.png", "a.txt"]);
+    assert!(text.contains(r#"<th><a href="?sort=size&amp;order=asc">Size</a> &#x25BC;</th>"#));
+    assert!(text.contains(r#"<th><a href="?sort=name&amp;order=asc">Name</a></th>"#));
 }
 
 #[tokio::test]
@@ -226,3 +287,1829 @@ async fn request_paths_with_spaces() {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     assert_ne!(response.text().await.unwrap(), "outside content");
 }
+
+#[tokio::test]
+async fn range_requests() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener.start(Options::default()).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+    let url = format!("{origin}/data.txt");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("accept-ranges").unwrap(), "bytes");
+
+    // Single range
+    let response = client
+        .get(&url)
+        .header("range", "bytes=2-5")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        "bytes 2-5/10"
+    );
+    assert_eq!(response.text().await.unwrap(), "2345");
+
+    // Suffix range
+    let response = client
+        .get(&url)
+        .header("range", "bytes=-3")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        "bytes 7-9/10"
+    );
+    assert_eq!(response.text().await.unwrap(), "789");
+
+    // Multiple ranges
+    let response = client
+        .get(&url)
+        .header("range", "bytes=0-1,8-")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let content_type = response
+        .headers()
+        .get("content-type")
+        .unwrap()
+        .to_str()
+        .unwrap();
+    let boundary = content_type
+        .strip_prefix("multipart/byteranges; boundary=")
+        .unwrap()
+        .to_string();
+    let text = response.text().await.unwrap();
+    assert_eq!(
+        text,
+        format!(
+            "--{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 0-1/10\r\n\r\n01\r\n\
+             --{boundary}\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Range: bytes 8-9/10\r\n\r\n89\r\n\
+             --{boundary}--\r\n"
+        )
+    );
+
+    // Overlapping and adjacent ranges are merged
+    let response = client
+        .get(&url)
+        .header("range", "bytes=4-6,0-2,1-3")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        "bytes 0-6/10"
+    );
+    assert_eq!(response.text().await.unwrap(), "0123456");
+
+    // Too many ranges are ignored
+    let response = client
+        .get(&url)
+        .header("range", format!("bytes={}", vec!["0-"; 200].join(",")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "0123456789");
+
+    // Unsatisfiable range
+    let response = client
+        .get(&url)
+        .header("range", "bytes=20-30")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(
+        response.headers().get("content-range").unwrap(),
+        "bytes */10"
+    );
+
+    // Stale `If-Range` falls back to the full file
+    let response = client
+        .get(&url)
+        .header("range", "bytes=2-5")
+        .header("if-range", "Thu, 01 Jan 1970 00:00:00 GMT")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "0123456789");
+}
+
+#[tokio::test]
+async fn conditional_requests() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        temp_dir.path().join("index.html"),
+        "<body><p>hello</p></body>",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener.start(Options::default()).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+    let url = format!("{origin}/data.txt");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers().get("etag").unwrap().clone();
+    let last_modified = response.headers().get("last-modified").unwrap().clone();
+
+    let response = client
+        .get(&url)
+        .header("if-none-match", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers().get("etag").unwrap(), &etag);
+
+    let response = client
+        .get(&url)
+        .header("if-modified-since", &last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    // `If-None-Match` takes precedence over `If-Modified-Since`
+    let response = client
+        .get(&url)
+        .header("if-none-match", "\"outdated\"")
+        .header("if-modified-since", &last_modified)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // `If-Range` with the current tag allows a partial response
+    let response = client
+        .get(&url)
+        .header("range", "bytes=0-1")
+        .header("if-range", &etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+
+    // Injected HTML gets a tag that depends on the injected script
+    let response = client.get(&origin).send().await.unwrap();
+    let page_etag = response.headers().get("etag").unwrap().clone();
+    let response = client
+        .get(&origin)
+        .header("if-none-match", &page_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let response = client
+        .get(format!("{origin}/?reload"))
+        .header("if-none-match", &page_etag)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_ne!(response.headers().get("etag").unwrap(), &page_etag);
+}
+
+#[tokio::test]
+async fn stream_large_files() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let content = (0..4 * 1024 * 1024)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<u8>>();
+    fs::write(temp_dir.path().join("data.bin"), &content).unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener.start(Options::default()).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+    let url = format!("{origin}/data.bin");
+
+    let response = client.get(&url).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-length").unwrap(),
+        &content.len().to_string()
+    );
+    assert_eq!(response.bytes().await.unwrap(), content);
+
+    let response = client
+        .get(&url)
+        .header("range", "bytes=1000000-1000009,3000000-3000009")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    let content_length = response.headers().get("content-length").unwrap().clone();
+    let body = response.bytes().await.unwrap();
+    assert_eq!(content_length, &body.len().to_string());
+    assert!(
+        body.windows(10)
+            .any(|window| window == &content[1000000..1000010])
+    );
+    assert!(
+        body.windows(10)
+            .any(|window| window == &content[3000000..3000010])
+    );
+}
+
+#[tokio::test]
+async fn compression() {
+    use async_compression::tokio::bufread::GzipDecoder;
+    use tokio::io::AsyncReadExt;
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let script = "console.log('compress me');\n".repeat(100);
+    fs::write(temp_dir.path().join("app.js"), &script).unwrap();
+    fs::write(temp_dir.path().join("lib.js"), "console.log('identity');").unwrap();
+    fs::write(temp_dir.path().join("lib.js.br"), "precompressed brotli").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                compression: true,
+                precompressed: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    // On-the-fly compression
+    let response = client
+        .get(format!("{origin}/app.js"))
+        .header("accept-encoding", "gzip, br;q=0.5")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers().get("content-encoding").unwrap(), "gzip");
+    assert_eq!(response.headers().get("vary").unwrap(), "accept-encoding");
+    assert!(
+        response
+            .headers()
+            .get("etag")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("W/")
+    );
+    let compressed = response.bytes().await.unwrap();
+    let mut text = String::new();
+    GzipDecoder::new(&compressed[..])
+        .read_to_string(&mut text)
+        .await
+        .unwrap();
+    assert_eq!(text, script);
+
+    // Clients without `Accept-Encoding` get the identity
+    let response = client.get(format!("{origin}/app.js")).send().await.unwrap();
+    assert!(response.headers().get("content-encoding").is_none());
+    assert_eq!(response.text().await.unwrap(), script);
+
+    // Precompressed sibling files
+    let response = client
+        .get(format!("{origin}/lib.js"))
+        .header("accept-encoding", "gzip, br")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers().get("content-encoding").unwrap(), "br");
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/javascript; charset=utf-8"
+    );
+    assert_eq!(response.text().await.unwrap(), "precompressed brotli");
+
+    // Range requests are answered from the identity
+    let response = client
+        .get(format!("{origin}/lib.js"))
+        .header("accept-encoding", "br")
+        .header("range", "bytes=0-6")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+    assert!(response.headers().get("content-encoding").is_none());
+    assert_eq!(response.text().await.unwrap(), "console");
+}
+
+#[tokio::test]
+async fn spa_fallback() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        temp_dir.path().join("index.html"),
+        "<body><p>app shell</p></body>",
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                spa: Some("index.html".into()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    // Client-side routes get the app shell with the live-reload script injected
+    let response = client
+        .get(format!("{origin}/dashboard/settings"))
+        .header("accept", "text/html,application/xhtml+xml")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let text = response.text().await.unwrap();
+    assert!(text.starts_with("<body><p>app shell</p>"));
+    assert!(text.contains("/live-server/client.js"));
+
+    // The soft reload of a client-side route must get the reload payload
+    let response = client
+        .get(format!("{origin}/dashboard/settings?reload"))
+        .header("accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let text = response.text().await.unwrap();
+    assert!(text.contains("/live-server/reload.js"));
+
+    // Missing assets are still real 404s
+    let response = client
+        .get(format!("{origin}/missing.js"))
+        .header("accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Requests that do not accept HTML are not routes
+    let response = client
+        .get(format!("{origin}/api/users"))
+        .header("accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn custom_error_pages() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        temp_dir.path().join("404.html"),
+        "<h1>Custom not found</h1>",
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener.start(Options::default()).await.unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(format!("{origin}/missing.html"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let text = response.text().await.unwrap();
+    assert!(text.starts_with("<h1>Custom not found</h1>"));
+    assert!(text.contains("/live-server/client.js"));
+
+    // Navigations to paths without an HTML extension get the page as well
+    let response = client
+        .get(format!("{origin}/missing"))
+        .header("accept", "text/html")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .starts_with("<h1>Custom not found</h1>")
+    );
+
+    // A failed reload must not look like a successful one
+    let response = client
+        .get(format!("{origin}/missing.html?reload"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let text = response.text().await.unwrap();
+    assert!(text.starts_with("<h1>Custom not found</h1>"));
+    assert!(!text.contains("<script"));
+
+    // Status codes without a custom page use the built-in one
+    let response = client
+        .get(format!("{origin}/%2E%2E%2Fmissing.html"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .starts_with("<!DOCTYPE html>")
+    );
+}
+
+#[tokio::test]
+async fn reverse_proxy() {
+    use axum::{
+        Router,
+        http::{HeaderMap, Uri, header},
+        response::Redirect,
+        routing::{get, post},
+    };
+
+    let upstream = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let upstream_addr = upstream.local_addr().unwrap();
+    let app =
+        Router::new()
+            .route(
+                "/api/echo",
+                get(|uri: Uri, headers: HeaderMap| async move {
+                    let host = headers.get(header::HOST).unwrap().to_str().unwrap();
+                    format!("{uri} {host}")
+                }),
+            )
+            .route("/api/upload", post(|body: String| async move { body }))
+            .route(
+                "/api/authorization",
+                get(|headers: HeaderMap| async move {
+                    match headers.get(header::AUTHORIZATION) {
+                        Some(value) => value.to_str().unwrap().to_string(),
+                        None => "none".to_string(),
+                    }
+                }),
+            )
+            .route(
+                "/api/redirect",
+                get(move || async move {
+                    Redirect::temporary(&format!("http://{upstream_addr}/api/echo"))
+                }),
+            )
+            .route(
+                "/api/redirect-port",
+                get(move || async move {
+                    Redirect::temporary(&format!("http://{upstream_addr}1/x"))
+                }),
+            )
+            .route(
+                "/v1/legacy/redirect",
+                get(move || async move {
+                    Redirect::temporary(&format!("http://{upstream_addr}/v1/legacy/echo"))
+                }),
+            )
+            .route(
+                "/v1/legacy/redirect-base",
+                get(move || async move {
+                    Redirect::temporary(&format!("http://{upstream_addr}/v10/x"))
+                }),
+            );
+    tokio::spawn(async move { axum::serve(upstream, app).await.unwrap() });
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>page</p>").unwrap();
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                proxies: vec![
+                    format!("/api=http://{upstream_addr}").parse().unwrap(),
+                    format!("/legacy=http://{upstream_addr}/v1")
+                        .parse()
+                        .unwrap(),
+                ],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+
+    // Path and query are forwarded, and `Host` is rewritten to the upstream
+    let response = client
+        .get(format!("{origin}/api/echo?page=2"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.text().await.unwrap(),
+        format!("/api/echo?page=2 {upstream_addr}")
+    );
+
+    // Methods and bodies are forwarded
+    let response = client
+        .post(format!("{origin}/api/upload"))
+        .body("uploaded content")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "uploaded content");
+
+    // Redirects to the upstream stay on the same origin
+    let response = client
+        .get(format!("{origin}/api/redirect"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers().get("location").unwrap(), "/api/echo");
+    let response = client
+        .get(format!("{origin}/legacy/redirect"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers().get("location").unwrap(), "/legacy/echo");
+
+    // Only whole ports and path segments are matched
+    let response = client
+        .get(format!("{origin}/api/redirect-port"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers().get("location").unwrap(),
+        &format!("http://{upstream_addr}1/x")
+    );
+    let response = client
+        .get(format!("{origin}/legacy/redirect-base"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.headers().get("location").unwrap(), "/v10/x");
+
+    // Other paths are still served from the root
+    let response = client.get(&origin).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().starts_with("<p>page</p>"));
+
+    // Credentials for the upstream are forwarded
+    let response = client
+        .get(format!("{origin}/api/authorization"))
+        .bearer_auth("token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "Bearer token");
+
+    // Credentials for live-server are not
+    let auth_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let auth_origin = auth_listener.link().unwrap();
+    tokio::spawn(async move {
+        auth_listener
+            .start(Options {
+                proxies: vec![format!("/api=http://{upstream_addr}").parse().unwrap()],
+                auth: Some("admin:secret".parse().unwrap()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let response = client
+        .get(format!("{auth_origin}/api/authorization"))
+        .basic_auth("admin", Some("secret"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "none");
+
+    // Prefixes that cannot be routed are rejected on start
+    for proxies in [
+        vec!["/api=http://127.0.0.1:9", "/api/=http://127.0.0.1:9"],
+        vec!["/live-server-ws=http://127.0.0.1:9"],
+        vec!["/live-server=http://127.0.0.1:9"],
+    ] {
+        let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+        let result = listener
+            .start(Options {
+                proxies: proxies.iter().map(|proxy| proxy.parse().unwrap()).collect(),
+                ..Default::default()
+            })
+            .await;
+        assert!(result.is_err(), "{proxies:?}");
+    }
+}
+
+#[tokio::test]
+async fn https_with_self_signed_certificate() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>secure</p>").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path())
+        .await
+        .unwrap()
+        .https(Certificate::SelfSigned)
+        .unwrap();
+    let origin = listener.link().unwrap();
+    assert!(origin.starts_with("https://127.0.0.1:"));
+    tokio::spawn(async move {
+        listener.start(Options::default()).await.unwrap();
+    });
+
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let response = client.get(&origin).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().starts_with("<p>secure</p>"));
+
+    // Plain HTTP is not served
+    let plain_origin = origin.replace("https://", "http://");
+    assert!(reqwest::get(plain_origin).await.is_err());
+}
+
+#[tokio::test]
+async fn custom_headers() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("fonts")).unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>page</p>").unwrap();
+    fs::write(temp_dir.path().join("app.wasm"), "wasm").unwrap();
+    fs::write(temp_dir.path().join("fonts/font.woff2"), "font").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                headers: vec![
+                    "X-Frame-Options: DENY".parse().unwrap(),
+                    "*.wasm=Cache-Control: no-store".parse().unwrap(),
+                    "/fonts/**=Access-Control-Allow-Origin: *".parse().unwrap(),
+                ],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+
+    let response = reqwest::get(&origin).await.unwrap();
+    assert_eq!(response.headers().get("x-frame-options").unwrap(), "DENY");
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-cache");
+    assert!(
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .is_none()
+    );
+
+    // Scoped rules replace the default headers
+    let response = reqwest::get(format!("{origin}/app.wasm")).await.unwrap();
+    assert_eq!(response.headers().get("cache-control").unwrap(), "no-store");
+    assert_eq!(response.headers().get("x-frame-options").unwrap(), "DENY");
+
+    let response = reqwest::get(format!("{origin}/fonts/font.woff2"))
+        .await
+        .unwrap();
+    assert_eq!(
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .unwrap(),
+        "*"
+    );
+
+    // Listings and error pages get the headers as well
+    let response = reqwest::get(format!("{origin}/fonts/")).await.unwrap();
+    assert_eq!(
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .unwrap(),
+        "*"
+    );
+    let response = reqwest::get(format!("{origin}/missing.html"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert_eq!(response.headers().get("x-frame-options").unwrap(), "DENY");
+}
+
+#[tokio::test]
+async fn clean_urls() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("docs")).unwrap();
+    fs::write(temp_dir.path().join("about.html"), "<p>about</p>").unwrap();
+    fs::write(temp_dir.path().join("docs.html"), "<p>docs page</p>").unwrap();
+    fs::write(temp_dir.path().join("docs/index.html"), "<p>docs index</p>").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                clean_urls: true,
+                redirect_html_extension: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let get = |path: &str| client.get(format!("{origin}{path}")).send();
+
+    let response = get("/about").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().starts_with("<p>about</p>"));
+
+    // `.html` files take precedence over directories without a trailing slash
+    let response = get("/docs").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .starts_with("<p>docs page</p>")
+    );
+    let response = get("/docs/").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(
+        response
+            .text()
+            .await
+            .unwrap()
+            .starts_with("<p>docs index</p>")
+    );
+
+    // Non-canonical URLs are redirected
+    let response = get("/about.html?lang=en").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(
+        response.headers().get("location").unwrap(),
+        "/about?lang=en"
+    );
+    let response = get("/about/").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers().get("location").unwrap(), "/about");
+    let response = get("/docs/index.html").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers().get("location").unwrap(), "/docs/");
+
+    // Traversal protection still applies
+    let response = get("/%2E%2E%2Fabout").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn mounts() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path().join("site");
+    let shared = temp_dir.path().join("shared");
+    fs::create_dir_all(root.join("assets")).unwrap();
+    fs::create_dir_all(shared.join("css")).unwrap();
+    fs::write(root.join("assets/local.txt"), "shadowed").unwrap();
+    fs::write(root.join("secret.txt"), "root").unwrap();
+    fs::write(shared.join("css/style.css"), "body {}").unwrap();
+
+    let listener = listen("127.0.0.1:0", &root).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                mounts: vec![format!("/assets={}", shared.display()).parse().unwrap()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap();
+    let get = |path: &str| client.get(format!("{origin}{path}")).send();
+
+    let response = get("/assets/css/style.css").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "body {}");
+
+    // The mount takes precedence over the directory of the same name in the root
+    let response = get("/assets/local.txt").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = get("/assets").await.unwrap();
+    assert_eq!(response.status(), StatusCode::TEMPORARY_REDIRECT);
+    assert_eq!(response.headers().get("location").unwrap(), "/assets/");
+    let response = get("/assets/").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.text().await.unwrap().contains("css"));
+
+    // Paths cannot escape the mounted directory
+    let response = get("/assets/%2E%2E%2Fsite%2Fsecret.txt").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn overlay_roots() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let dist = temp_dir.path().join("dist");
+    let public = temp_dir.path().join("public");
+    fs::create_dir_all(dist.join("js")).unwrap();
+    fs::create_dir_all(public.join("js")).unwrap();
+    fs::write(dist.join("js/app.js"), "built").unwrap();
+    fs::write(public.join("js/app.js"), "stale").unwrap();
+    fs::write(public.join("js/vendor.js"), "vendor").unwrap();
+    fs::write(public.join("robots.txt"), "robots").unwrap();
+
+    let listener = listen("127.0.0.1:0", &dist).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                overlays: vec![public],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let get = |path: &str| reqwest::get(format!("{origin}{path}"));
+
+    // The first root containing the file wins
+    let response = get("/js/app.js").await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "built");
+    let response = get("/js/vendor.js").await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "vendor");
+    let response = get("/robots.txt").await.unwrap();
+    assert_eq!(response.text().await.unwrap(), "robots");
+    let response = get("/missing.txt").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    // Directory listings merge the entries of all roots
+    let response = get("/js/").await.unwrap();
+    let text = response.text().await.unwrap();
+    assert_eq!(text.matches("app.js</a>").count(), 1);
+    assert!(text.contains("vendor.js</a>"));
+}
+
+#[tokio::test]
+async fn basic_auth() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>secret</p>").unwrap();
+    let htpasswd = temp_dir.path().join(".htpasswd");
+    let bcrypt_hash = bcrypt::hash("bcrypt-password", 4).unwrap();
+    fs::write(
+        &htpasswd,
+        format!("# users\nsha:{{SHA}}5en6G6MezRroT3XKqkdPOmY/BfQ=\nbcrypt:{bcrypt_hash}\n"),
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                auth: Some(BasicAuth::from_htpasswd(htpasswd).unwrap()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+
+    let response = client.get(&origin).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(
+        response.headers().get("www-authenticate").unwrap(),
+        r#"Basic realm="live-server", charset="UTF-8""#
+    );
+    let response = client
+        .get(&origin)
+        .basic_auth("sha", Some("wrong"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    for (user, password) in [("sha", "secret"), ("bcrypt", "bcrypt-password")] {
+        let response = client
+            .get(&origin)
+            .basic_auth(user, Some(password))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(response.text().await.unwrap().starts_with("<p>secret</p>"));
+    }
+
+    // The websocket is protected as well
+    let response = client
+        .get(format!("{origin}/live-server-ws"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let auth: BasicAuth = "admin:p:ss".parse().unwrap();
+    assert!(auth.verify("admin", "p:ss"));
+    assert!("no-password".parse::<BasicAuth>().is_err());
+}
+
+#[tokio::test]
+async fn host_allowlist() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<p>hello</p>").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                allowed_hosts: vec!["dev.test".to_string(), ".preview.test".to_string()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let origin_url = origin;
+    let client = reqwest::Client::new();
+    let get_with_header = |path: &str, name: &'static str, value: &str| {
+        client
+            .get(format!("{origin_url}{path}"))
+            .header(name, value)
+            .send()
+    };
+
+    for host in [
+        "localhost:8080",
+        "app.localhost",
+        "127.0.0.1",
+        "[::1]:8080",
+        "192.168.1.20:8080",
+        "dev.test:8080",
+        "pr-1.preview.test",
+    ] {
+        let response = get_with_header("/", "host", host).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK, "{host}");
+    }
+    for host in ["evil.test", "dev.test.evil.test", "localhost.evil.test"] {
+        let response = get_with_header("/", "host", host).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{host}");
+    }
+
+    // Websocket upgrades from other websites are rejected
+    let upgrade = |origin: &str| {
+        client
+            .get(format!("{origin_url}/live-server-ws"))
+            .header("connection", "upgrade")
+            .header("upgrade", "websocket")
+            .header("sec-websocket-version", "13")
+            .header("sec-websocket-key", "dGhlIHNhbXBsZSBub25jZQ==")
+            .header("origin", origin)
+            .send()
+    };
+    for origin in [
+        "http://evil.test",
+        "http://203.0.113.5",
+        "http://localhost:8080",
+    ] {
+        let response = upgrade(origin).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN, "{origin}");
+    }
+    for origin in [origin_url.as_str(), "http://dev.test:8080"] {
+        let response = upgrade(origin).await.unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::SWITCHING_PROTOCOLS,
+            "{origin}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn script_injection_position() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let tricky = concat!(
+        "<html><HEAD><title></head></title></HEAD><body>",
+        "<!-- </body> -->",
+        "<script>const s = \"</body>\";</script>",
+        "<template><p></body></p></template>",
+        "<p class='a>b'>text</p>",
+        "</BODY>\n</html>\n",
+    );
+    fs::write(temp_dir.path().join("tricky.html"), tricky).unwrap();
+    fs::write(temp_dir.path().join("fragment.html"), "<p>fragment</p>").unwrap();
+    fs::write(
+        temp_dir.path().join("no-head.html"),
+        "<body><p>no head</p></body>",
+    )
+    .unwrap();
+
+    let body_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let body_origin = body_listener.link().unwrap();
+    tokio::spawn(async move { body_listener.start(Options::default()).await.unwrap() });
+    let head_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let head_origin = head_listener.link().unwrap();
+    tokio::spawn(async move {
+        head_listener
+            .start(Options {
+                script_position: "head".parse().unwrap(),
+                ..Default::default()
+            })
+            .await
+            .unwrap()
+    });
+    let get = |origin: &str, path: &str| {
+        let url = format!("{origin}{path}");
+        async move { reqwest::get(url).await.unwrap().text().await.unwrap() }
+    };
+    let script_index = |text: &str| text.find("<script src=").unwrap();
+
+    let text = get(&body_origin, "/tricky.html").await;
+    assert_eq!(
+        script_index(&text),
+        tricky.find("</BODY>").unwrap(),
+        "{text}"
+    );
+    let text = get(&head_origin, "/tricky.html").await;
+    assert_eq!(script_index(&text), tricky.find("</HEAD>").unwrap());
+
+    // Fall back to the other tag, then to the end of the document
+    let text = get(&head_origin, "/no-head.html").await;
+    assert!(text.ends_with(r#"data-hard="false"></script></body>"#));
+    let text = reqwest::Client::new()
+        .get(format!("{body_origin}/fragment.html"))
+        .header("sec-fetch-dest", "document")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.starts_with("<p>fragment</p><script src="));
+    assert!(text.ends_with("</script>"));
+}
+
+#[tokio::test]
+async fn skip_injection_for_fragments() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("emails")).unwrap();
+    fs::write(
+        temp_dir.path().join("page.html"),
+        "<body><p>page</p></body>",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("fragment.html"), "<p>fragment</p>").unwrap();
+    fs::write(
+        temp_dir.path().join("404.html"),
+        "<body><p>missing</p></body>",
+    )
+    .unwrap();
+    fs::write(
+        temp_dir.path().join("emails/welcome.html"),
+        "<body><p>welcome</p></body>",
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                no_inject: vec!["/emails/**".into()],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+    let get = |path: &str, header: Option<(&'static str, &'static str)>| {
+        let mut request = client.get(format!("{origin}{path}"));
+        if let Some((name, value)) = header {
+            request = request.header(name, value);
+        }
+        async move { request.send().await.unwrap().text().await.unwrap() }
+    };
+
+    // Documents are injected, fragments without a fetch destination are not
+    let text = get("/page.html", None).await;
+    assert!(text.contains("/live-server/client.js"));
+    let text = get("/fragment.html", None).await;
+    assert_eq!(text, "<p>fragment</p>");
+
+    // Requests of HTMX, Turbo Frames and `fetch()` never get the script
+    for header in [
+        ("hx-request", "true"),
+        ("turbo-frame", "content"),
+        ("sec-fetch-dest", "empty"),
+    ] {
+        let text = get("/page.html", Some(header)).await;
+        assert_eq!(text, "<body><p>page</p></body>", "{header:?}");
+    }
+    let text = get("/page.html", Some(("sec-fetch-dest", "iframe"))).await;
+    assert!(text.contains("/live-server/client.js"));
+
+    let response = client
+        .get(format!("{origin}/page.html"))
+        .send()
+        .await
+        .unwrap();
+    let vary = response
+        .headers()
+        .get_all("vary")
+        .iter()
+        .collect::<Vec<_>>();
+    assert!(
+        vary.iter()
+            .any(|v| v.to_str().unwrap().contains("HX-Request"))
+    );
+
+    // Excluded paths are served as they are, even to navigations
+    let text = get("/emails/welcome.html", Some(("sec-fetch-dest", "document"))).await;
+    assert_eq!(text, "<body><p>welcome</p></body>");
+
+    // Error pages follow the same rules
+    for header in [
+        ("hx-request", "true"),
+        ("turbo-frame", "content"),
+        ("sec-fetch-dest", "empty"),
+    ] {
+        let text = get("/partials/missing.html", Some(header)).await;
+        assert_eq!(text, "<body><p>missing</p></body>", "{header:?}");
+    }
+    let text = get("/emails/missing.html", Some(("sec-fetch-dest", "document"))).await;
+    assert_eq!(text, "<body><p>missing</p></body>");
+    let text = get(
+        "/partials/missing.html",
+        Some(("sec-fetch-dest", "document")),
+    )
+    .await;
+    assert!(text.contains("/live-server/client.js"));
+}
+
+#[tokio::test]
+async fn legacy_charsets() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    // "テスト" in Shift_JIS
+    let mut shift_jis = b"<html><head><meta charset=\"Shift_JIS\"></head><body>".to_vec();
+    shift_jis.extend_from_slice(&[0x83, 0x65, 0x83, 0x58, 0x83, 0x67]);
+    shift_jis.extend_from_slice(b"</body></html>");
+    fs::write(temp_dir.path().join("sjis.html"), &shift_jis).unwrap();
+    let windows_1252 = b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=windows-1252\"><body>caf\xe9</body>";
+    fs::write(temp_dir.path().join("cp1252.html"), windows_1252).unwrap();
+    fs::write(
+        temp_dir.path().join("unknown.html"),
+        b"<body>caf\xe9</body>",
+    )
+    .unwrap();
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend(
+        "<body>テスト</body>"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes),
+    );
+    fs::write(temp_dir.path().join("utf16.html"), utf16).unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move { listener.start(Options::default()).await.unwrap() });
+    let get = |path: &str| reqwest::get(format!("{origin}{path}"));
+
+    let response = get("/sjis.html").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=shift_jis"
+    );
+    let body = response.bytes().await.unwrap();
+    let script_start = body.windows(8).position(|w| w == b"<script ").unwrap();
+    assert_eq!(&body[..script_start], &shift_jis[..shift_jis.len() - 14]);
+    assert!(body.ends_with(b"</script></body></html>"));
+
+    let response = get("/cp1252.html").await.unwrap();
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=windows-1252"
+    );
+    let body = response.bytes().await.unwrap();
+    let content_end = windows_1252.len() - "</body>".len();
+    assert!(body.starts_with(&windows_1252[..content_end]));
+    assert!(body.ends_with(b"</script></body>"));
+
+    // The browser detects the encoding of documents without declarations
+    let response = get("/unknown.html").await.unwrap();
+    assert_eq!(response.headers().get("content-type").unwrap(), "text/html");
+
+    let response = get("/utf16.html").await.unwrap();
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let text = response.text().await.unwrap();
+    assert!(text.starts_with("<body>テスト<script src="));
+}
+
+#[tokio::test]
+async fn content_security_policy() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(
+        temp_dir.path().join("meta.html"),
+        concat!(
+            "<head><meta http-equiv='Content-Security-Policy' ",
+            "content=\"default-src 'none'; script-src 'nonce-r4nd0m'\">",
+            "<script nonce=\"r4nd0m\">console.log(1)</script></head><body></body>",
+        ),
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("header.html"), "<body></body>").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                headers: vec![
+                    "/header.html=Content-Security-Policy: default-src 'none'; img-src *"
+                        .parse()
+                        .unwrap(),
+                ],
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+
+    // The nonce of the page is reused and the meta policy allows the client
+    let text = reqwest::get(format!("{origin}/meta.html"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains(concat!(
+        "content=\"default-src 'none'; script-src 'nonce-r4nd0m' 'self'; ",
+        "connect-src 'self'; frame-src 'self'\">"
+    )));
+    assert!(text.contains(r#"<script nonce="r4nd0m" src="/live-server/client.js""#));
+
+    let response = reqwest::get(format!("{origin}/header.html")).await.unwrap();
+    assert_eq!(
+        response.headers().get("content-security-policy").unwrap(),
+        "default-src 'none'; img-src *; script-src 'self'; connect-src 'self'; frame-src 'self'"
+    );
+}
+
+#[tokio::test]
+async fn access_log() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let log_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<body>hello</body>").unwrap();
+    fs::write(temp_dir.path().join("data.txt"), "0123456789").unwrap();
+
+    let json_path = log_dir.path().join("access.json");
+    let json_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let json_origin = json_listener.link().unwrap();
+    let access_log_file = Some(json_path.clone());
+    tokio::spawn(async move {
+        json_listener
+            .start(Options {
+                access_log: Some("json".parse().unwrap()),
+                access_log_file,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let common_path = log_dir.path().join("access.log");
+    let common_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let common_origin = common_listener.link().unwrap();
+    let access_log_file = Some(common_path.clone());
+    tokio::spawn(async move {
+        common_listener
+            .start(Options {
+                access_log: Some("common".parse().unwrap()),
+                access_log_file,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+    // Entries are written once the response body is dropped by the server.
+    let read_lines = |path: PathBuf, count: usize| async move {
+        for _ in 0..50 {
+            let log = fs::read_to_string(&path).unwrap_or_default();
+            if log.lines().count() >= count {
+                return log.lines().map(str::to_string).collect::<Vec<_>>();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Expected {count} lines in {path:?}");
+    };
+
+    let response = client
+        .get(format!("{json_origin}/data.txt?v=1"))
+        .header("user-agent", "phone")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.text().await.unwrap(), "0123456789");
+    let response = client
+        .get(format!("{json_origin}/missing.js"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    response.bytes().await.unwrap();
+
+    let lines = read_lines(json_path, 2).await;
+    let entry: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+    assert_eq!(entry["method"], "GET");
+    assert_eq!(entry["path"], "/data.txt?v=1");
+    assert_eq!(entry["status"], 200);
+    assert_eq!(entry["bytes"], 10);
+    assert_eq!(entry["client"], "127.0.0.1");
+    assert_eq!(entry["user_agent"], "phone");
+    assert!(entry["duration_ms"].is_f64());
+    let entry: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+    assert_eq!(entry["status"], 404);
+
+    client
+        .get(format!("{common_origin}/data.txt"))
+        .send()
+        .await
+        .unwrap()
+        .bytes()
+        .await
+        .unwrap();
+    let lines = read_lines(common_path, 1).await;
+    assert!(lines[0].starts_with("127.0.0.1 - - ["), "{}", lines[0]);
+    assert!(lines[0].ends_with(r#"+0000] "GET /data.txt HTTP/1.1" 200 10"#));
+}
+
+#[tokio::test]
+async fn json_index_listing() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let nested_dir = temp_dir.path().join("sub dir/deep");
+    fs::create_dir_all(&nested_dir).unwrap();
+    fs::write(temp_dir.path().join("index.html"), "<body>home</body>").unwrap();
+    fs::write(temp_dir.path().join("a.txt"), "abc").unwrap();
+    fs::write(temp_dir.path().join(".hidden"), "").unwrap();
+    fs::write(temp_dir.path().join("sub dir/b.txt"), "b").unwrap();
+    fs::write(nested_dir.join("c.txt"), "c").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                index_listing: true,
+                auto_ignore: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let client = reqwest::Client::new();
+    let paths = |listing: &serde_json::Value| {
+        listing["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| entry["path"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>()
+    };
+
+    // Directories with an index page are listed as JSON for tools
+    let response = client
+        .get(&origin)
+        .header("accept", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "application/json"
+    );
+    assert_eq!(response.headers().get("vary").unwrap(), "Accept");
+    let listing: serde_json::Value = serde_json::from_str(&response.text().await.unwrap()).unwrap();
+    assert_eq!(listing["path"], "/");
+    assert_eq!(paths(&listing), ["sub dir/", "a.txt", "index.html"]);
+    let entries = listing["entries"].as_array().unwrap();
+    assert_eq!(entries[0]["type"], "directory");
+    assert_eq!(entries[0]["url"], "/sub%20dir/");
+    assert!(entries[0]["size"].is_null());
+    assert_eq!(entries[1]["type"], "file");
+    assert_eq!(entries[1]["size"], 3);
+    assert!(entries[1]["mtime"].is_u64());
+
+    // Subdirectories are listed after their entry, up to the depth limit
+    let listing: serde_json::Value = client
+        .get(format!("{origin}/?format=json&recursive"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .map(|text| serde_json::from_str(&text).unwrap())
+        .unwrap();
+    assert_eq!(
+        paths(&listing),
+        [
+            "sub dir/",
+            "sub dir/deep/",
+            "sub dir/deep/c.txt",
+            "sub dir/b.txt",
+            "a.txt",
+            "index.html"
+        ]
+    );
+    assert_eq!(listing["entries"][2]["url"], "/sub%20dir/deep/c.txt");
+    let listing: serde_json::Value = client
+        .get(format!("{origin}/sub%20dir/?format=json&depth=1"))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .map(|text| serde_json::from_str(&text).unwrap())
+        .unwrap();
+    assert_eq!(paths(&listing), ["deep/", "b.txt"]);
+
+    // Browsers still get the index page
+    let text = reqwest::get(&origin).await.unwrap().text().await.unwrap();
+    assert!(text.starts_with("<body>home<script src="));
+}
+
+#[tokio::test]
+async fn markdown_rendering() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::create_dir(temp_dir.path().join("docs")).unwrap();
+    fs::write(
+        temp_dir.path().join("docs/README.md"),
+        concat!(
+            "# Docs\n\n",
+            "| a | b |\n| - | - |\n| 1 | 2 |\n\n",
+            "- [x] done\n- [ ] todo\n\n",
+            "```rust\nfn main() {}\n```\n\n",
+            "```\n<plain>\n```\n",
+        ),
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("notes.md"), "*notes*").unwrap();
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                markdown: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let raw_listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let raw_origin = raw_listener.link().unwrap();
+    tokio::spawn(async move { raw_listener.start(Options::default()).await.unwrap() });
+
+    // README.md is the index of directories without index.html
+    let response = reqwest::get(format!("{origin}/docs/")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let text = response.text().await.unwrap();
+    assert!(text.contains("<title>README.md</title>"));
+    assert!(text.contains("<h1>Docs</h1>"));
+    assert!(text.contains("<table>"));
+    assert!(text.contains(r#"<input disabled="" type="checkbox" checked=""/>"#));
+    // Code blocks of known languages are highlighted, others are escaped
+    assert!(text.contains("<pre style="));
+    assert!(text.contains(">main</span>"));
+    assert!(text.contains("<pre><code>&lt;plain&gt;\n</code></pre>"));
+    assert!(text.contains("/live-server/client.js"));
+
+    let text = reqwest::get(format!("{origin}/notes.md"))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("<p><em>notes</em></p>"));
+
+    // Markdown is served as it is unless enabled
+    let response = reqwest::get(format!("{raw_origin}/notes.md"))
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/markdown; charset=utf-8"
+    );
+    assert_eq!(response.text().await.unwrap(), "*notes*");
+}
+
+#[tokio::test]
+async fn server_side_includes() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path().join("site");
+    fs::create_dir_all(root.join("partials")).unwrap();
+    fs::create_dir_all(root.join(".partials")).unwrap();
+    fs::write(temp_dir.path().join("secret.html"), "TOP SECRET").unwrap();
+    fs::write(
+        root.join("index.html"),
+        concat!(
+            "<body><!--#include file=\"partials/nav.html\" --><p>home</p>",
+            "<!--#include virtual=\"/.partials/footer.html\" --></body>",
+        ),
+    )
+    .unwrap();
+    fs::write(
+        root.join("partials/nav.html"),
+        "<nav><!--#include file='../partials/item.html' --></nav>",
+    )
+    .unwrap();
+    fs::write(root.join("partials/item.html"), "<a>item</a>").unwrap();
+    fs::write(root.join(".partials/footer.html"), "<footer>v1</footer>").unwrap();
+    fs::write(
+        root.join("broken.html"),
+        concat!(
+            "<!--#include file=\"broken.html\" -->",
+            "<!--#include file=\"../secret.html\" -->",
+        ),
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", &root).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                includes: true,
+                auto_ignore: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+    let get = |path: &str| {
+        let url = format!("{origin}{path}");
+        async move { reqwest::get(url).await.unwrap().text().await.unwrap() }
+    };
+
+    let text = get("/").await;
+    assert!(
+        text.starts_with("<body><nav><a>item</a></nav><p>home</p><footer>v1</footer><script"),
+        "{text}"
+    );
+
+    // Cycles and paths outside of the root are left as comments
+    let text = get("/broken.html").await;
+    assert!(text.contains("<!-- live-server: Include cycle of broken.html -->"));
+    assert!(text.contains("is outside of the root -->"));
+    assert!(!text.contains("TOP SECRET"));
+
+    // Changing an included file reloads even if it is ignored
+    let mut stream = tokio::net::TcpStream::connect(origin.trim_start_matches("http://"))
+        .await
+        .unwrap();
+    stream
+        .write_all(
+            concat!(
+                "GET /live-server-ws HTTP/1.1\r\n",
+                "host: localhost\r\n",
+                "connection: upgrade\r\n",
+                "upgrade: websocket\r\n",
+                "sec-websocket-version: 13\r\n",
+                "sec-websocket-key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+    let mut buffer = [0; 1024];
+    let read = stream.read(&mut buffer).await.unwrap();
+    assert!(buffer[..read].starts_with(b"HTTP/1.1 101"));
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    fs::write(root.join(".partials/footer.html"), "<footer>v2</footer>").unwrap();
+    let read = tokio::time::timeout(Duration::from_secs(5), stream.read(&mut buffer))
+        .await
+        .expect("Expected a reload message")
+        .unwrap();
+    assert!(read > 0);
+    assert!(get("/").await.contains("<footer>v2</footer>"));
+}
+
+#[tokio::test]
+async fn html_templates() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    let root = temp_dir.path();
+    fs::create_dir_all(root.join("data")).unwrap();
+    fs::create_dir_all(root.join("layouts")).unwrap();
+    fs::write(root.join("data/site.json"), r#"{"title": "<Acme>"}"#).unwrap();
+    fs::write(root.join("data/team.yaml"), "- Ann\n- Bob\n").unwrap();
+    fs::write(root.join("data/meta.toml"), "year = 2026\n").unwrap();
+    // "テスト" in Shift_JIS
+    let mut shift_jis = b"<html><head><meta charset=\"Shift_JIS\"></head><body>".to_vec();
+    shift_jis.extend_from_slice(&[0x83, 0x65, 0x83, 0x58, 0x83, 0x67]);
+    shift_jis.extend_from_slice(b"</body></html>");
+    fs::write(root.join("sjis.html"), &shift_jis).unwrap();
+    fs::write(
+        root.join("layouts/base.html"),
+        "<html><body>{% block content %}{% endblock %}</body></html>",
+    )
+    .unwrap();
+    fs::write(
+        root.join("index.html"),
+        concat!(
+            r#"{% extends "layouts/base.html" %}{% block content %}"#,
+            "<h1>{{ data.site.title }}</h1>",
+            "{% for name in data.team %}<li>{{ name }}</li>{% endfor %}",
+            "{% if data.meta.year > 2000 %}<p>{{ data.meta.year }}</p>{% endif %}",
+            "<p>{{ env.CARGO_PKG_NAME }}</p>",
+            "{% endblock %}",
+        ),
+    )
+    .unwrap();
+    fs::write(
+        root.join("broken.html"),
+        "<p>\n{{ data.site.title | nope }}</p>",
+    )
+    .unwrap();
+
+    let listener = listen("127.0.0.1:0", root).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                templates: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+
+    let text = reqwest::get(&origin).await.unwrap().text().await.unwrap();
+    assert!(
+        text.starts_with(concat!(
+            "<html><body><h1>&lt;Acme&gt;</h1><li>Ann</li><li>Bob</li>",
+            "<p>2026</p><p>live-server</p><script"
+        )),
+        "{text}"
+    );
+
+    // Errors show where they are
+    let response = reqwest::get(format!("{origin}/broken.html")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let text = response.text().await.unwrap();
+    assert!(text.contains("unknown filter"), "{text}");
+    assert!(text.contains("(in broken.html:2)"));
+    assert!(text.contains("2 &gt; {{ data.site.title | nope }}"));
+    assert!(!text.contains("Referenced variables"));
+    assert!(text.contains("/live-server/client.js"));
+    let text = reqwest::Client::new()
+        .get(format!("{origin}/broken.html"))
+        .header("hx-request", "true")
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(text.contains("unknown filter"));
+    assert!(!text.contains("/live-server/client.js"));
+
+    // Pages in other encodings are not rendered rather than garbled
+    let response = reqwest::get(format!("{origin}/sjis.html")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        response.headers().get("content-type").unwrap(),
+        "text/html; charset=utf-8"
+    );
+    let text = response.text().await.unwrap();
+    assert!(text.contains("Templates must be encoded in UTF-8, but sjis.html is not"));
+    assert!(!text.contains('\u{FFFD}'));
+}
+
+#[tokio::test]
+async fn mime_overrides() {
+    let temp_dir = tempfile::tempdir().unwrap();
+    fs::write(temp_dir.path().join("app.ts"), "export {};").unwrap();
+    fs::write(temp_dir.path().join("model.glb"), "glTF").unwrap();
+    fs::write(temp_dir.path().join("data.unknown"), "data").unwrap();
+    fs::write(
+        temp_dir.path().join("page.xhtml"),
+        "<html><body></body></html>",
+    )
+    .unwrap();
+    fs::write(temp_dir.path().join("LICENSE"), "Permission is granted").unwrap();
+    fs::write(
+        temp_dir.path().join("page"),
+        "\n<!DOCTYPE html><html><body></body></html>",
+    )
+    .unwrap();
+    let mime_file = temp_dir.path().join("mime.types");
+    fs::write(
+        &mime_file,
+        "# Comment\nmodel/gltf-binary glb;\nts=video/mp2t\n",
+    )
+    .unwrap();
+
+    let mut mime_types = MimeOverride::from_file(&mime_file).unwrap();
+    mime_types.push(".TS=text/javascript".parse().unwrap());
+    assert!("ts".parse::<MimeOverride>().is_err());
+    assert!("=text/plain".parse::<MimeOverride>().is_err());
+
+    let listener = listen("127.0.0.1:0", temp_dir.path()).await.unwrap();
+    let origin = listener.link().unwrap();
+    tokio::spawn(async move {
+        listener
+            .start(Options {
+                mime_types,
+                default_mime: "application/octet-stream".parse().unwrap(),
+                sniff_mime: true,
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+    });
+
+    let content_type = |path: &'static str| {
+        let origin = origin.clone();
+        async move {
+            let response = reqwest::get(format!("{origin}/{path}")).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let content_type = response.headers()["content-type"].to_str().unwrap();
+            (content_type.to_string(), response.text().await.unwrap())
+        }
+    };
+
+    // Later overrides take precedence
+    let (mime, _) = content_type("app.ts").await;
+    assert_eq!(mime, "text/javascript; charset=utf-8");
+    let (mime, _) = content_type("model.glb").await;
+    assert_eq!(mime, "model/gltf-binary");
+    let (mime, _) = content_type("data.unknown").await;
+    assert_eq!(mime, "application/octet-stream");
+
+    // Files without an extension are sniffed, and sniffed HTML gets the script
+    let (mime, text) = content_type("LICENSE").await;
+    assert_eq!(mime, "text/plain; charset=utf-8");
+    assert_eq!(text, "Permission is granted");
+    let (mime, text) = content_type("page").await;
+    assert_eq!(mime, "text/html; charset=utf-8");
+    assert!(text.contains("/live-server/client.js"));
+
+    // XHTML keeps its type and gets the script
+    let (mime, text) = content_type("page.xhtml").await;
+    assert_eq!(mime, "application/xhtml+xml; charset=utf-8");
+    assert!(text.contains("/live-server/client.js"));
+}